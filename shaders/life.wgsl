@@ -32,14 +32,16 @@ var<storage, read> input_buffer: array<u32>; // this is used as both ininpudt an
 
 @group(0)
 @binding(2)
-var<storage, write> output_buffer: array<u32>; // this is used as both input and output for convenience
+var<storage, read_write> output_buffer: array<u32>; // this is used as both input and output for convenience
 
 @group(0)
 @binding(3)
 var output_texture: texture_storage_2d<rgba32float, write>;
 
 fn modulus(a: i32, b: i32) -> i32 {
-    return ((a % b) + b) % b;
+    // `%` with a negative operand is undefined once translated to GLSL, so
+    // only ever take it of a positive number. Offsets are never below -b.
+    return (a + b) % b;
 }
 
 fn from_xy(x: u32, y: u32) -> u32 {
@@ -58,9 +60,21 @@ fn get_at(position: vec3<u32>, x_mod: i32, y_mod: i32) -> u32 {
     }
 }
 
+fn colour(value: u32) -> vec4<f32> {
+    return vec4<f32>(
+      f32(value) / f32(params.lifetime), 
+      f32(value) / f32(params.lifetime), 
+      f32(value) / f32(params.lifetime), 
+      1.0
+    );
+}
+
 @compute
 @workgroup_size(16, 16)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if global_id.x >= params.width || global_id.y >= params.height {
+        return;
+    }
 
     let index = from_xy(global_id.x, global_id.y);
     let old_value = input_buffer[index];
@@ -114,14 +128,18 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
     output_buffer[index] = new_value;
 
-    textureStore(
-      output_texture, 
-      vec2<u32>(global_id.x, global_id.y), 
-      vec4<f32>(
-        f32(new_value) / f32(params.lifetime), 
-        f32(new_value) / f32(params.lifetime), 
-        f32(new_value) / f32(params.lifetime), 
-        1.0
-      )
-    );
+    textureStore(output_texture, vec2<u32>(global_id.x, global_id.y), colour(new_value));
+}
+
+// Colours the texture from the input buffer without stepping, for when the
+// cells were written from the CPU.
+@compute
+@workgroup_size(16, 16)
+fn draw(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if global_id.x >= params.width || global_id.y >= params.height {
+        return;
+    }
+
+    let value = input_buffer[from_xy(global_id.x, global_id.y)];
+    textureStore(output_texture, vec2<u32>(global_id.x, global_id.y), colour(value));
 }
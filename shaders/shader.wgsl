@@ -50,14 +50,61 @@ fn hsv_to_rgb(hsv: vec3<f32>) -> vec3<f32> {
     return vec3<f32>(r, g, b);
 }
 
+struct Camera {
+    // Cell in the middle of the viewport
+    centre: vec2<f32>,
+    // Viewport size in pixels
+    viewport: vec2<f32>,
+    // Grid size in cells
+    grid: vec2<f32>,
+    // Cells per pixel
+    scale: f32,
+    _padding: f32,
+}
+
 @group(0) @binding(0)
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+@group(0) @binding(2)
+var<uniform> camera: Camera;
+
+struct Overlay {
+    // Cell under the top left corner of the stamp
+    stamp_origin: vec2<f32>,
+    // Stamp size in cells, zero when there's no preview
+    stamp_size: vec2<f32>,
+    selection_origin: vec2<f32>,
+    // Selection size in cells, zero when nothing is selected
+    selection_size: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var t_stamp: texture_2d<f32>;
+@group(1) @binding(1)
+var<uniform> overlay: Overlay;
+
+// Offset of `cell` from `origin`, wrapped the same way as the grid.
+fn wrapped_offset(cell: vec2<f32>, origin: vec2<f32>) -> vec2<f32> {
+    let offset = floor(cell) - origin;
+    return offset - floor(offset / camera.grid) * camera.grid;
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let life = textureSample(t_diffuse, s_diffuse, in.tex_coords).x;
-    let new_color = hsv_to_rgb(vec3<f32>(life - 0.01, 1.0, 1.0));
+    // The fragment position is in pixels from the top left, so row 0 of the
+    // grid is at the top. The sampler repeats, wrapping the grid around.
+    let cell = camera.centre + (in.clip_position.xy - camera.viewport * 0.5) * camera.scale;
+    let life = textureSample(t_diffuse, s_diffuse, cell / camera.grid).x;
+    var new_color = hsv_to_rgb(vec3<f32>(life - 0.01, 1.0, 1.0));
+
+    if (all(wrapped_offset(cell, overlay.selection_origin) < overlay.selection_size)) {
+        new_color = mix(new_color, vec3<f32>(0.3, 0.6, 1.0), 0.4);
+    }
+    let stamp = wrapped_offset(cell, overlay.stamp_origin);
+    if (all(stamp < overlay.stamp_size)) {
+        let alive = textureLoad(t_stamp, vec2<i32>(stamp), 0).x;
+        new_color = mix(new_color * 0.3, vec3<f32>(1.0), alive * 0.8);
+    }
     return vec4<f32>(new_color, 1.0);
 }
@@ -0,0 +1,78 @@
+//! Animated GIF export.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::image::{Image, ImageError};
+
+/// NeuQuant sampling factor for building each frame's palette, from 1 (best)
+/// to 30 (fastest). 10 is the usual trade off.
+const QUANTISATION_SPEED: i32 = 10;
+
+/// An animated GIF being written frame by frame. Every frame gets its own
+/// 256 colour palette, quantised from the full colour image.
+pub struct Animation {
+    encoder: gif::Encoder<BufWriter<File>>,
+    width: u32,
+    height: u32,
+    /// Delay between frames in hundredths of a second, the unit GIF uses.
+    delay: u16,
+    frames: u64,
+}
+
+impl Animation {
+    /// Starts a looping GIF of `width` by `height` frames shown `delay_ms`
+    /// milliseconds apart. GIFs only store hundredths of a second.
+    pub fn create(
+        path: impl AsRef<Path>,
+        width: u32,
+        height: u32,
+        delay_ms: u32,
+    ) -> Result<Self, ImageError> {
+        let too_large = || ImageError::Gif(format!("{width}x{height} is too large for a GIF"));
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(
+            file,
+            width.try_into().map_err(|_| too_large())?,
+            height.try_into().map_err(|_| too_large())?,
+            &[],
+        )?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        Ok(Self {
+            encoder,
+            width,
+            height,
+            delay: (delay_ms / 10).min(u16::MAX as u32) as u16,
+            frames: 0,
+        })
+    }
+
+    /// Number of frames written so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Appends `image`, which has to be the size given to `create`.
+    pub fn push(&mut self, image: &Image) -> Result<(), ImageError> {
+        if (image.width(), image.height()) != (self.width, self.height) {
+            return Err(ImageError::Gif(format!(
+                "frame is {}x{} but the animation is {}x{}",
+                image.width(),
+                image.height(),
+                self.width,
+                self.height
+            )));
+        }
+        let mut frame = gif::Frame::from_rgb_speed(
+            self.width as u16,
+            self.height as u16,
+            image.pixels(),
+            QUANTISATION_SPEED,
+        );
+        frame.delay = self.delay;
+        self.encoder.write_frame(&frame)?;
+        self.frames += 1;
+        Ok(())
+    }
+}
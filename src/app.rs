@@ -0,0 +1,1246 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use conway::{
+    gpu, presets, render, seed, Camera, Format, Grid, History, Library, Pattern, Resize, Rule,
+    Simulation, Snapshot,
+};
+use wgpu::{Device, Queue};
+use wgpu::{Surface, SurfaceConfiguration};
+use winit::dpi::PhysicalSize;
+use winit::{
+    event::*,
+    event_loop::{ControlFlow, EventLoop},
+    window::{Fullscreen, Window, WindowBuilder},
+};
+
+use crate::cli;
+
+/// Generations a single frame will run at most, so a rate the engine can't
+/// keep up with doesn't build an ever growing backlog.
+const MAX_GENERATIONS_PER_FRAME: f64 = 1000.0;
+
+/// How much one notch of the mouse wheel zooms by.
+const ZOOM_STEP: f64 = 1.25;
+
+/// Fraction of the window the arrow keys pan by.
+const PAN_STEP: f64 = 0.125;
+
+/// Range the +/- keys can change the rate within, in generations per second.
+const RATE_RANGE: (f64, f64) = (1.0 / 64.0, 1_000_000.0);
+
+struct State {
+    window: Window,
+    surface: Surface,
+    window_config: SurfaceConfiguration,
+    window_size: PhysicalSize<u32>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    renderer: render::Renderer,
+    camera: Camera,
+    /// Whether the camera refits the grid when the window is resized, until
+    /// it's panned or zoomed.
+    fitted: bool,
+    /// Last known cursor position in the window.
+    cursor: (f64, f64),
+    /// Whether the middle mouse button is held down, dragging the view.
+    panning: bool,
+    /// Value the cells under the cursor are set to while the left (alive) or
+    /// right (empty) button is held down.
+    brush: Option<u32>,
+    /// Last cell the brush touched, so fast drags draw unbroken lines.
+    last_cell: Option<(i64, i64)>,
+    modifiers: ModifiersState,
+    library: Library,
+    /// Library entry the stamp tool places.
+    stamp: usize,
+    /// That entry as rotated and flipped so far.
+    stamp_pattern: Pattern,
+    /// Whether left clicks stamp instead of draw, with a preview following
+    /// the cursor.
+    stamping: bool,
+    /// Whether left drags select a rectangle instead of drawing.
+    selecting: bool,
+    /// Selected `x, y, width, height`, the corner not necessarily wrapped
+    /// onto the grid.
+    selection: Option<(i64, i64, u32, u32)>,
+    /// Cell the selection drag started from, while the button is held.
+    selection_start: Option<(i64, i64)>,
+    /// Cells last copied or cut, decay included.
+    clipboard: Option<Grid>,
+    life: Box<dyn Simulation>,
+    history: History,
+    /// How the cells are kept when the grid is resized.
+    resize_mode: Resize,
+    /// Size of a cell in screen pixels while the grid follows the window
+    /// size.
+    follow_window: Option<f64>,
+    /// Generation the simulation started at, which resetting goes back to.
+    start_generation: u64,
+    /// Settings for reseeding, those of the starting soup if there was one.
+    soup: seed::Soup,
+    last_frame: Instant,
+    frame_delay: Duration,
+    /// Target generations per second.
+    rate: f64,
+    /// Generations due but not yet run, carried between frames so rates
+    /// slower than the frame rate still advance.
+    owed: f64,
+    /// Whether the rate was found to be more than `MAX_GENERATIONS_PER_FRAME`
+    /// allows, so the user is only told once for each rate.
+    rate_limited: bool,
+    args: cli::Args,
+    rule: Rule,
+    /// Lifetime for rules that don't have their own number of states.
+    lifetime: u32,
+    /// Rule or preset name being typed in, after Enter was pressed.
+    rule_entry: Option<String>,
+    presets: presets::Presets,
+    preset: Option<usize>,
+    paused: bool,
+    title: String,
+}
+
+impl State {
+    async fn new(window: Window, args: cli::Args, setup: cli::Setup) -> Self {
+        let cli::Setup {
+            rule,
+            params,
+            data,
+            generation,
+            presets,
+            preset,
+            library,
+            soup,
+            ..
+        } = setup;
+
+        // UNIVERSAL GPU INITIALISATION
+        let instance = args.instance();
+        let window_size = window.inner_size();
+        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+        let (adapter, device, queue) =
+            gpu::request_device(&instance, Some(&surface), args.software).await;
+        println!("{:#?}", adapter.limits());
+
+        // SETTING UNIVERSAL WINDOW STUFF (no move)
+        let surface_caps = surface.get_capabilities(&adapter);
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| f.describe().srgb)
+            .unwrap_or(surface_caps.formats[0]);
+        let window_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: window_size.width,
+            height: window_size.height,
+            present_mode: surface_caps.present_modes[0],
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats: vec![],
+        };
+        surface.configure(&device, &window_config);
+
+        // INIT COMPUTE SHADER (move to life.rs)
+        let device = Arc::new(device);
+        let queue = Arc::new(queue);
+        let mut life = args.simulation(data, params, Some((device.clone(), queue.clone())));
+        life.set_generation(generation);
+
+        // SHARED BETWEEN LIFE AND RENDERER
+        let output_texture_view = life
+            .output_texture()
+            .expect("simulation has no output texture")
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        // PURE RENDERER STUFF
+        let camera = Camera::fit(
+            (params.width, params.height),
+            (window_size.width, window_size.height),
+        );
+        let renderer =
+            render::Renderer::new(&device, output_texture_view, window_config.format, &camera);
+
+        let last_frame = Instant::now();
+        let frame_delay = Duration::from_millis(args.frame_delay as u64);
+        let paused = args.paused;
+
+        let mut state = Self {
+            window,
+            surface,
+            window_config,
+            window_size,
+            device,
+            queue,
+            renderer,
+            camera,
+            fitted: true,
+            cursor: (0.0, 0.0),
+            panning: false,
+            brush: None,
+            last_cell: None,
+            modifiers: ModifiersState::empty(),
+            stamp_pattern: library[0].pattern.clone(),
+            library,
+            stamp: 0,
+            stamping: false,
+            selecting: false,
+            selection: None,
+            selection_start: None,
+            clipboard: None,
+            life,
+            history: History::new(args.rewind_every, args.history as usize),
+            resize_mode: args.resize_mode.resize(),
+            follow_window: args.follow_window.then(|| 1.0 / camera.scale()),
+            start_generation: generation,
+            soup: soup.unwrap_or(seed::Soup {
+                density: args.density,
+                random_lifetimes: args.random_lifetimes,
+                ..seed::Soup::new(0)
+            }),
+            last_frame,
+            frame_delay,
+            rate: args.rate,
+            owed: 0.0,
+            rate_limited: false,
+            lifetime: match rule.states {
+                Some(_) => args.lifetime,
+                None => params.lifetime(),
+            },
+            rule_entry: None,
+            args,
+            rule,
+            presets,
+            preset,
+            paused,
+            title: String::new(),
+        };
+        state.follow_window();
+        state
+    }
+
+    fn window(&self) -> &Window {
+        &self.window
+    }
+
+    /// When the next frame is due, going by --frame-delay.
+    fn next_frame(&self) -> Instant {
+        self.last_frame + self.frame_delay
+    }
+
+    /// Runs however many generations the rate says are due since the last
+    /// frame, all in one batch.
+    async fn update(&mut self) {
+        let elapsed = self.last_frame.elapsed();
+        log::debug!(
+            "{}ms since last update ({} fps)",
+            elapsed.as_micros() as f32 / 1000.0,
+            1.0 / elapsed.as_secs_f32()
+        );
+        self.last_frame = Instant::now();
+        if !self.paused {
+            let owed = self.owed + elapsed.as_secs_f64() * self.rate;
+            if owed > MAX_GENERATIONS_PER_FRAME && !self.rate_limited {
+                println!(
+                    "can't keep up with {} generations/s, running at most \
+                     {MAX_GENERATIONS_PER_FRAME} generations a frame",
+                    self.rate
+                );
+                self.rate_limited = true;
+            }
+            self.owed = owed.min(MAX_GENERATIONS_PER_FRAME);
+            let due = self.owed.floor();
+            if due >= 1.0 {
+                self.history.step(self.life.as_mut(), due as u32);
+                self.owed -= due;
+            }
+        }
+        self.update_title();
+    }
+
+    /// Multiplies the rate by `factor`, within `RATE_RANGE`.
+    fn change_rate(&mut self, factor: f64) {
+        self.rate = (self.rate * factor).clamp(RATE_RANGE.0, RATE_RANGE.1);
+        self.rate_limited = false;
+        println!("rate: {} generations/s", self.rate);
+        self.update_title();
+    }
+
+    /// Shows the generation, rate and whether the simulation is paused in the
+    /// title bar.
+    fn update_title(&mut self) {
+        if let Some(entry) = &self.rule_entry {
+            let title = format!("Rule or preset: {entry}_ (Enter to apply, Esc to cancel)");
+            if title != self.title {
+                self.window.set_title(&title);
+                self.title = title;
+            }
+            return;
+        }
+        let mut title = format!(
+            "Conway's Game of Life - generation {} - {} generations/s",
+            self.life.generation(),
+            self.rate
+        );
+        if self.paused {
+            title.push_str(" (paused)");
+        }
+        // Only touch the window when something changed, not every frame.
+        if title != self.title {
+            self.window.set_title(&title);
+            self.title = title;
+        }
+    }
+
+    /// Advances `n` generations at once, whether or not the simulation is
+    /// paused.
+    fn advance(&mut self, n: u32) {
+        self.history.step(self.life.as_mut(), n);
+        self.update_title();
+    }
+
+    /// Steps back `n` generations, pausing so they aren't run again straight
+    /// away.
+    fn rewind(&mut self, n: u64) {
+        if self.history.rewind(self.life.as_mut(), n) == 0 {
+            println!("can't rewind any further");
+        } else {
+            self.sync_rule();
+        }
+        self.paused = true;
+        self.update_title();
+    }
+
+    fn undo(&mut self) {
+        if self.history.undo(self.life.as_mut()) {
+            self.sync_rule();
+        } else {
+            println!("nothing to undo");
+        }
+        self.update_title();
+    }
+
+    fn redo(&mut self) {
+        if self.history.redo(self.life.as_mut()) {
+            self.sync_rule();
+        } else {
+            println!("nothing to redo");
+        }
+        self.update_title();
+    }
+
+    /// Goes back to the starting grid, keeping the current rule.
+    fn reset(&mut self) {
+        self.history.record_jump(self.life.as_mut());
+        self.life.reset();
+        self.life.set_generation(self.start_generation);
+        self.update_title();
+    }
+
+    /// Fills the grid with a new soup, like the starting one but with a fresh
+    /// seed.
+    fn reseed(&mut self) {
+        let params = self.life.params();
+        let side = |n: u32| self.soup.size.map_or(n, |side| side.min(n));
+        if self.soup.symmetry.needs_square() && side(params.width) != side(params.height) {
+            println!("the soup's symmetry needs a square grid");
+            return;
+        }
+        self.soup.seed = rand::random();
+        let cells = seed::soup(params.width, params.height, params.lifetime(), &self.soup);
+        self.history.record(self.life.as_mut());
+        self.life.write_cells(&cells);
+        println!("seed: {}", self.soup.seed);
+    }
+
+    fn clear(&mut self) {
+        let params = self.life.params();
+        self.history.record(self.life.as_mut());
+        self.life
+            .write_cells(&seed::empty(params.width, params.height));
+    }
+
+    /// Picks the rule and lifetime back up from the simulation after
+    /// restoring a snapshot. The params don't say whether the rule had a
+    /// number of states, so it stays a Generations rule if it is one now.
+    fn sync_rule(&mut self) {
+        let params = self.life.params();
+        let (birth, survival) = (params.dead_rules(), params.alive_rules());
+        let rule = match (self.rule.states, params.lifetime().checked_add(1)) {
+            (Some(_), Some(states)) => Rule::generations(birth, survival, states),
+            _ => {
+                self.lifetime = params.lifetime();
+                Rule::new(birth, survival)
+            }
+        };
+        if rule != self.rule {
+            self.rule = rule;
+            self.preset = None;
+        }
+    }
+
+    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        if new_size.width > 0 && new_size.height > 0 {
+            self.window_size = new_size;
+            self.window_config.width = new_size.width;
+            self.window_config.height = new_size.height;
+            self.surface.configure(&self.device, &self.window_config);
+            self.camera.set_viewport((new_size.width, new_size.height));
+            if self.fitted {
+                self.camera.fit_grid();
+            }
+            self.renderer.set_camera(&self.queue, &self.camera);
+            self.follow_window();
+        }
+    }
+
+    /// Changes the grid size, keeping the cells as the resize mode says. The
+    /// undo history and selection are dropped since they no longer fit.
+    fn resize_grid(&mut self, width: u32, height: u32) {
+        let max_side = self.device.limits().max_texture_dimension_2d;
+        let (width, height) = (width.clamp(1, max_side), height.clamp(1, max_side));
+        if let Err(e) = gpu::check_grid_size(&self.device, width, height) {
+            println!("{e}");
+            return;
+        }
+        let params = self.life.params();
+        if (width, height) == (params.width, params.height) {
+            return;
+        }
+        self.life.resize_to(width, height, self.resize_mode);
+        self.history.clear();
+        self.select(None);
+        let output_texture_view = self
+            .life
+            .output_texture()
+            .expect("simulation has no output texture")
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.renderer
+            .set_output_texture(&self.device, output_texture_view);
+        let fitted = self.fitted;
+        self.move_camera(
+            |camera| {
+                camera.set_grid((width, height));
+                if fitted {
+                    camera.fit_grid();
+                }
+            },
+            fitted,
+        );
+        self.update_title();
+        println!("grid: {width}x{height}");
+    }
+
+    /// Resizes the grid by `(x, y)` factors, no longer following the window.
+    fn scale_grid(&mut self, x: f64, y: f64) {
+        self.follow_window = None;
+        let params = self.life.params();
+        let scale = |n: u32, factor: f64| (n as f64 * factor).round() as u32;
+        self.resize_grid(scale(params.width, x), scale(params.height, y));
+    }
+
+    /// Sizes the grid to fill the window, if it's following it.
+    fn follow_window(&mut self) {
+        let Some(cell_size) = self.follow_window else {
+            return;
+        };
+        let size = self.window_size;
+        self.fitted = true;
+        self.resize_grid(
+            (size.width as f64 / cell_size).ceil() as u32,
+            (size.height as f64 / cell_size).ceil() as u32,
+        );
+    }
+
+    fn toggle_follow_window(&mut self) {
+        self.follow_window = match self.follow_window {
+            Some(_) => None,
+            None => Some(1.0 / self.camera.scale()),
+        };
+        self.follow_window();
+    }
+
+    fn cycle_resize_mode(&mut self) {
+        self.resize_mode = match self.resize_mode {
+            Resize::Crop => Resize::Centre,
+            Resize::Centre => Resize::Scale,
+            Resize::Scale => Resize::Crop,
+        };
+        println!("resize mode: {:?}", self.resize_mode);
+    }
+
+    /// Applies a change to the camera. Any change other than fitting the grid
+    /// stops it following the window size.
+    fn move_camera(&mut self, change: impl FnOnce(&mut Camera), fitted: bool) {
+        change(&mut self.camera);
+        self.fitted = fitted;
+        self.renderer.set_camera(&self.queue, &self.camera);
+        self.move_stamp();
+    }
+
+    /// The grid cell under the cursor, not wrapped onto the grid.
+    fn cursor_cell(&self) -> (i64, i64) {
+        let (x, y) = self.camera.screen_to_cell(self.cursor);
+        (x.floor() as i64, y.floor() as i64)
+    }
+
+    /// Paints with the brush from the last cell it touched to the one under
+    /// the cursor.
+    fn paint(&mut self) {
+        let Some(value) = self.brush else {
+            return;
+        };
+        let to = self.cursor_cell();
+        let from = self.last_cell.unwrap_or(to);
+        let params = self.life.params();
+        let positions = line(from, to)
+            .map(|(x, y)| {
+                (
+                    x.rem_euclid(params.width as i64) as u32,
+                    y.rem_euclid(params.height as i64) as u32,
+                )
+            })
+            .collect::<Vec<_>>();
+        self.history.touch();
+        self.life.set_cells(&positions, value);
+        self.last_cell = Some(to);
+    }
+
+    /// Turns the stamp tool on or off.
+    fn toggle_stamping(&mut self) {
+        if self.selecting {
+            self.toggle_selecting();
+        }
+        self.stamping = !self.stamping;
+        let pattern = self.stamping.then_some(&self.stamp_pattern);
+        self.renderer.set_stamp(&self.device, &self.queue, pattern);
+        self.move_stamp();
+        if self.stamping {
+            println!("stamp: {}", self.library[self.stamp].name);
+        }
+    }
+
+    /// Moves through the library by `offset`, starting the new pattern
+    /// unrotated.
+    fn cycle_stamp(&mut self, offset: isize) {
+        let len = self.library.len() as isize;
+        self.stamp = (self.stamp as isize + offset).rem_euclid(len) as usize;
+        self.set_stamp_pattern(self.library[self.stamp].pattern.clone());
+        println!("stamp: {}", self.library[self.stamp].name);
+    }
+
+    fn set_stamp_pattern(&mut self, pattern: Pattern) {
+        self.stamp_pattern = pattern;
+        if self.stamping {
+            self.renderer
+                .set_stamp(&self.device, &self.queue, Some(&self.stamp_pattern));
+            self.move_stamp();
+        }
+    }
+
+    /// Top left cell of the stamp when it's centred on the cursor.
+    fn stamp_origin(&self) -> (i64, i64) {
+        let (x, y) = self.cursor_cell();
+        (
+            x - self.stamp_pattern.width() as i64 / 2,
+            y - self.stamp_pattern.height() as i64 / 2,
+        )
+    }
+
+    /// Keeps the preview under the cursor.
+    fn move_stamp(&mut self) {
+        if self.stamping {
+            self.renderer
+                .set_stamp_origin(&self.queue, self.stamp_origin());
+        }
+    }
+
+    /// Writes the stamp into the grid under the cursor, its dead cells
+    /// clearing whatever was there.
+    fn place_stamp(&mut self) {
+        let params = self.life.params();
+        let (x, y) = self.stamp_origin();
+        let pattern = &self.stamp_pattern;
+        // Lowest values first, so that live cells win where a stamp larger
+        // than the grid overlaps itself.
+        let mut values = BTreeMap::<u32, Vec<_>>::new();
+        for py in 0..pattern.height() {
+            for px in 0..pattern.width() {
+                let position = (
+                    (x + px as i64).rem_euclid(params.width as i64) as u32,
+                    (y + py as i64).rem_euclid(params.height as i64) as u32,
+                );
+                values
+                    .entry(pattern.value(px, py, params.lifetime()))
+                    .or_default()
+                    .push(position);
+            }
+        }
+        self.history.record(self.life.as_mut());
+        for (value, positions) in &values {
+            self.life.set_cells(positions, *value);
+        }
+    }
+
+    /// Turns the selection tool on or off, dropping the selection when off.
+    fn toggle_selecting(&mut self) {
+        if self.stamping {
+            self.toggle_stamping();
+        }
+        self.selecting = !self.selecting;
+        if !self.selecting {
+            self.select(None);
+        }
+    }
+
+    fn select(&mut self, selection: Option<(i64, i64, u32, u32)>) {
+        self.selection = selection;
+        self.renderer.set_selection(&self.queue, selection);
+    }
+
+    /// Stretches the selection from where the drag started to the cell under
+    /// the cursor, at most the size of the grid.
+    fn drag_selection(&mut self) {
+        let Some(start) = self.selection_start else {
+            return;
+        };
+        let params = self.life.params();
+        let end = self.cursor_cell();
+        let size = |a: i64, b: i64, max: u32| ((a - b).unsigned_abs() + 1).min(max as u64) as u32;
+        self.select(Some((
+            start.0.min(end.0),
+            start.1.min(end.1),
+            size(start.0, end.0, params.width),
+            size(start.1, end.1, params.height),
+        )));
+    }
+
+    /// The selected cells, or `None` with a message when nothing is selected.
+    fn selected(&mut self) -> Option<((i64, i64), Grid)> {
+        let Some((x, y, width, height)) = self.selection else {
+            println!("nothing selected, press S and drag to select");
+            return None;
+        };
+        Some(((x, y), self.life.read_region(x, y, width, height)))
+    }
+
+    /// Copies the selection to the clipboard, printing it as RLE too.
+    /// Returns false if nothing is selected.
+    fn copy(&mut self) -> bool {
+        let Some((_, cells)) = self.selected() else {
+            return false;
+        };
+        let lifetime = self.life.params().lifetime();
+        let mut pattern = Pattern::from_grid(&cells, 0, 0, cells.width(), cells.height(), lifetime);
+        pattern.rule = Some(self.rule.to_string());
+        print!("{}", pattern.write(Format::Rle));
+        self.clipboard = Some(cells);
+        true
+    }
+
+    /// Writes the clipboard with its top left corner under the cursor. A
+    /// clipboard copied before the grid shrank is cropped to fit it.
+    fn paste(&mut self) {
+        let Some(cells) = &self.clipboard else {
+            println!("nothing to paste, copy a selection with Ctrl+C first");
+            return;
+        };
+        let (x, y) = self.cursor_cell();
+        let params = self.life.params();
+        let (width, height) = (
+            cells.width().min(params.width),
+            cells.height().min(params.height),
+        );
+        let cells = if (width, height) == (cells.width(), cells.height()) {
+            cells.clone()
+        } else {
+            println!(
+                "the clipboard is {}x{}, pasting the top left {width}x{height} that fits the grid",
+                cells.width(),
+                cells.height()
+            );
+            cells.region(0, 0, width, height)
+        };
+        self.history.record(self.life.as_mut());
+        self.life.write_region(x, y, &cells);
+    }
+
+    /// Replaces every selected cell with `change(cell)`.
+    fn edit_selection(&mut self, change: impl Fn(u32) -> u32) {
+        let Some(((x, y), mut cells)) = self.selected() else {
+            return;
+        };
+        for cell in cells.cells_mut() {
+            *cell = change(*cell);
+        }
+        self.history.record(self.life.as_mut());
+        self.life.write_region(x, y, &cells);
+    }
+
+    /// Makes live cells dead and everything else alive.
+    fn invert_selection(&mut self) {
+        let lifetime = self.life.params().lifetime();
+        self.edit_selection(|cell| if cell == lifetime { 0 } else { lifetime });
+    }
+
+    /// Fills the selection with a new random soup at --density.
+    fn randomize_selection(&mut self) {
+        let Some(((x, y), cells)) = self.selected() else {
+            return;
+        };
+        let soup = seed::Soup {
+            density: self.args.density,
+            random_lifetimes: self.args.random_lifetimes,
+            ..seed::Soup::new(rand::random())
+        };
+        let (width, height) = (cells.width(), cells.height());
+        let lifetime = self.life.params().lifetime();
+        let soup = Grid::from_cells(width, height, seed::soup(width, height, lifetime, &soup));
+        self.history.record(self.life.as_mut());
+        self.life.write_region(x, y, &soup);
+    }
+
+    fn pan_step(&mut self, x: f64, y: f64) {
+        let size = self.window_size;
+        let delta = (
+            x * size.width as f64 * PAN_STEP,
+            y * size.height as f64 * PAN_STEP,
+        );
+        self.move_camera(|camera| camera.pan(delta), false);
+    }
+
+    /// Moves through the preset list by `offset`, keeping the current grid.
+    fn cycle_preset(&mut self, offset: isize) {
+        if self.presets.is_empty() {
+            return;
+        }
+        let len = self.presets.len() as isize;
+        let index = match self.preset {
+            Some(i) => (i as isize + offset).rem_euclid(len),
+            None if offset < 0 => len - 1,
+            None => 0,
+        } as usize;
+        self.use_preset(index);
+    }
+
+    fn use_preset(&mut self, index: usize) {
+        let preset = self.presets[index].clone();
+        let lifetime = preset.lifetime.unwrap_or(self.lifetime);
+        self.set_rule(preset.rule, lifetime);
+        self.preset = Some(index);
+        println!("rule: {preset}");
+    }
+
+    /// Switches rule and lifetime, rescaling the cells if the lifetime
+    /// changes.
+    fn set_rule(&mut self, rule: Rule, lifetime: u32) {
+        self.history.record(self.life.as_mut());
+        self.life.set_rule(&rule, lifetime);
+        self.rule = rule;
+        self.preset = None;
+    }
+
+    /// Multiplies the lifetime by `factor`, at least 1.
+    fn change_lifetime(&mut self, factor: f64) {
+        if self.rule.states.is_some() {
+            println!("{} has its own number of states", self.rule);
+            return;
+        }
+        let current = self.life.params().lifetime();
+        self.lifetime = (current as f64 * factor)
+            .round()
+            .clamp(1.0, u32::MAX as f64) as u32;
+        let preset = self.preset;
+        self.set_rule(self.rule, self.lifetime);
+        self.preset = preset;
+        println!("lifetime: {}", self.lifetime);
+    }
+
+    /// Handles typing in a rule after Enter, returning whether `event` was
+    /// part of it. Every key goes to the entry until it's applied or
+    /// cancelled.
+    fn enter_rule(&mut self, event: &WindowEvent) -> bool {
+        let Some(entry) = &mut self.rule_entry else {
+            return false;
+        };
+        match event {
+            WindowEvent::ReceivedCharacter(c) if !c.is_control() => entry.push(*c),
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match key {
+                VirtualKeyCode::Back => {
+                    entry.pop();
+                }
+                VirtualKeyCode::Escape => self.rule_entry = None,
+                VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                    let entry = self.rule_entry.take().unwrap();
+                    self.apply_rule_entry(entry.trim());
+                }
+                _ => {}
+            },
+            WindowEvent::ReceivedCharacter(_) | WindowEvent::KeyboardInput { .. } => {}
+            _ => return false,
+        }
+        self.update_title();
+        true
+    }
+
+    /// Switches to the rule or preset named by `entry`.
+    fn apply_rule_entry(&mut self, entry: &str) {
+        if entry.is_empty() {
+            return;
+        }
+        match entry.parse::<Rule>() {
+            Ok(rule) => {
+                self.set_rule(rule, self.lifetime);
+                println!("rule: {rule}");
+            }
+            Err(e) => match self.presets.position(entry) {
+                Some(index) => self.use_preset(index),
+                None => println!("'{entry}' is neither a rule nor a preset: {e}"),
+            },
+        }
+    }
+
+    /// Saves the whole grid as an RLE pattern named after the generation.
+    fn export_pattern(&mut self) {
+        let params = self.life.params();
+        let grid = self.life.read_grid();
+        let mut pattern =
+            Pattern::from_grid(&grid, 0, 0, params.width, params.height, params.lifetime());
+        pattern.rule = Some(self.rule.to_string());
+        let path = format!("conway-{}.rle", self.life.generation());
+        match pattern.save(&path) {
+            Ok(()) => println!("saved {path}"),
+            Err(e) => log::error!("failed to save {path}: {e}"),
+        }
+    }
+
+    /// Saves the current generation as a PNG named after it.
+    fn export_png(&mut self) {
+        let path = format!("conway-{}.png", self.life.generation());
+        let image = self.args.image(self.life.as_mut());
+        match image.and_then(|image| image.save_png(&path)) {
+            Ok(()) => println!("saved {path}"),
+            Err(e) => log::error!("failed to save {path}: {e}"),
+        }
+    }
+
+    fn save_state(&mut self) {
+        let path = self.args.state_path();
+        let snapshot = Snapshot {
+            states: self.rule.states,
+            ..Snapshot::capture(self.life.as_mut())
+        };
+        match snapshot.save(&path) {
+            Ok(()) => println!("saved {}", path.display()),
+            Err(e) => log::error!("failed to save {}: {e}", path.display()),
+        }
+    }
+
+    fn load_state(&mut self) {
+        let path = self.args.state_path();
+        let snapshot = match Snapshot::load(&path) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                log::error!("failed to load {}: {e}", path.display());
+                return;
+            }
+        };
+        let (params, current) = (snapshot.params, self.life.params());
+        if (params.width, params.height) != (current.width, current.height) {
+            log::error!(
+                "{} is {}x{} but the grid is {}x{}",
+                path.display(),
+                params.width,
+                params.height,
+                current.width,
+                current.height
+            );
+            return;
+        }
+        self.history.record_jump(self.life.as_mut());
+        snapshot.restore(self.life.as_mut());
+        self.rule = snapshot.rule();
+        if self.rule.states.is_none() {
+            self.lifetime = params.lifetime();
+        }
+        self.preset = None;
+        println!(
+            "loaded {} at generation {}",
+            path.display(),
+            snapshot.generation
+        );
+    }
+
+    fn input(&mut self, event: &WindowEvent) -> bool {
+        if self.enter_rule(event) {
+            return true;
+        }
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match key {
+                VirtualKeyCode::Space => {
+                    self.paused = !self.paused;
+                    self.update_title();
+                    true
+                }
+                VirtualKeyCode::Period => {
+                    self.advance(1);
+                    true
+                }
+                VirtualKeyCode::N => {
+                    self.advance(self.args.step_size);
+                    true
+                }
+                VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                    self.change_rate(2.0);
+                    true
+                }
+                VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                    self.change_rate(0.5);
+                    true
+                }
+                VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                    self.rule_entry = Some(String::new());
+                    self.update_title();
+                    true
+                }
+                VirtualKeyCode::L => {
+                    self.change_lifetime(if self.modifiers.shift() { 0.5 } else { 2.0 });
+                    true
+                }
+                VirtualKeyCode::LBracket => {
+                    self.cycle_preset(-1);
+                    true
+                }
+                VirtualKeyCode::RBracket => {
+                    self.cycle_preset(1);
+                    true
+                }
+                VirtualKeyCode::F2 => {
+                    self.export_png();
+                    true
+                }
+                VirtualKeyCode::F => {
+                    self.move_camera(Camera::fit_grid, true);
+                    true
+                }
+                VirtualKeyCode::O => {
+                    self.move_camera(Camera::one_to_one, false);
+                    true
+                }
+                // With Ctrl the arrows make the grid narrower, wider, shorter
+                // or taller.
+                VirtualKeyCode::Left if self.modifiers.ctrl() => {
+                    self.scale_grid(0.5, 1.0);
+                    true
+                }
+                VirtualKeyCode::Right if self.modifiers.ctrl() => {
+                    self.scale_grid(2.0, 1.0);
+                    true
+                }
+                VirtualKeyCode::Up if self.modifiers.ctrl() => {
+                    self.scale_grid(1.0, 0.5);
+                    true
+                }
+                VirtualKeyCode::Down if self.modifiers.ctrl() => {
+                    self.scale_grid(1.0, 2.0);
+                    true
+                }
+                VirtualKeyCode::W => {
+                    self.toggle_follow_window();
+                    true
+                }
+                VirtualKeyCode::M => {
+                    self.cycle_resize_mode();
+                    true
+                }
+                // Arrows move the view, so the grid moves the other way.
+                VirtualKeyCode::Left => {
+                    self.pan_step(1.0, 0.0);
+                    true
+                }
+                VirtualKeyCode::Right => {
+                    self.pan_step(-1.0, 0.0);
+                    true
+                }
+                VirtualKeyCode::Up => {
+                    self.pan_step(0.0, 1.0);
+                    true
+                }
+                VirtualKeyCode::Down => {
+                    self.pan_step(0.0, -1.0);
+                    true
+                }
+                VirtualKeyCode::F5 => {
+                    self.save_state();
+                    true
+                }
+                VirtualKeyCode::F9 => {
+                    self.load_state();
+                    true
+                }
+                VirtualKeyCode::F3 => {
+                    self.reset();
+                    true
+                }
+                VirtualKeyCode::F4 => {
+                    self.reseed();
+                    true
+                }
+                VirtualKeyCode::F7 => {
+                    self.clear();
+                    true
+                }
+                VirtualKeyCode::F6 => {
+                    self.export_pattern();
+                    true
+                }
+                VirtualKeyCode::T => {
+                    self.toggle_stamping();
+                    true
+                }
+                VirtualKeyCode::Tab => {
+                    self.cycle_stamp(if self.modifiers.shift() { -1 } else { 1 });
+                    true
+                }
+                VirtualKeyCode::R => {
+                    self.set_stamp_pattern(self.stamp_pattern.rotated());
+                    true
+                }
+                VirtualKeyCode::S => {
+                    self.toggle_selecting();
+                    true
+                }
+                VirtualKeyCode::C if self.modifiers.ctrl() => {
+                    self.copy();
+                    true
+                }
+                VirtualKeyCode::X if self.modifiers.ctrl() => {
+                    if self.copy() {
+                        self.edit_selection(|_| 0);
+                    }
+                    true
+                }
+                VirtualKeyCode::V if self.modifiers.ctrl() => {
+                    self.paste();
+                    true
+                }
+                VirtualKeyCode::Z if self.modifiers.ctrl() && self.modifiers.shift() => {
+                    self.redo();
+                    true
+                }
+                VirtualKeyCode::Z if self.modifiers.ctrl() => {
+                    self.undo();
+                    true
+                }
+                VirtualKeyCode::Y if self.modifiers.ctrl() => {
+                    self.redo();
+                    true
+                }
+                VirtualKeyCode::Comma => {
+                    self.rewind(1);
+                    true
+                }
+                VirtualKeyCode::B => {
+                    self.rewind(self.args.step_size as u64);
+                    true
+                }
+                VirtualKeyCode::Delete | VirtualKeyCode::Back => {
+                    self.edit_selection(|_| 0);
+                    true
+                }
+                VirtualKeyCode::I => {
+                    self.invert_selection();
+                    true
+                }
+                VirtualKeyCode::G => {
+                    self.randomize_selection();
+                    true
+                }
+                VirtualKeyCode::X => {
+                    self.set_stamp_pattern(self.stamp_pattern.flipped_horizontally());
+                    true
+                }
+                VirtualKeyCode::Y => {
+                    self.set_stamp_pattern(self.stamp_pattern.flipped_vertically());
+                    true
+                }
+                _ => false,
+            },
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = (position.x, position.y);
+                if self.panning {
+                    let delta = (position.0 - self.cursor.0, position.1 - self.cursor.1);
+                    self.move_camera(|camera| camera.pan(delta), false);
+                }
+                self.cursor = position;
+                self.paint();
+                self.move_stamp();
+                self.drag_selection();
+                true
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.stamping => {
+                self.place_stamp();
+                true
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } if self.selecting => {
+                self.selection_start =
+                    (*state == ElementState::Pressed).then(|| self.cursor_cell());
+                self.drag_selection();
+                true
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: button @ (MouseButton::Left | MouseButton::Right),
+                ..
+            } => {
+                self.brush = match (state, button) {
+                    (ElementState::Released, _) => None,
+                    (ElementState::Pressed, MouseButton::Left) => {
+                        Some(self.life.params().lifetime())
+                    }
+                    (ElementState::Pressed, _) => Some(0),
+                };
+                // A whole stroke undoes in one go.
+                if self.brush.is_some() {
+                    self.history.record(self.life.as_mut());
+                }
+                self.last_cell = None;
+                self.paint();
+                true
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Middle,
+                ..
+            } => {
+                self.panning = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let notches = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                    // Touchpads scroll in pixels, roughly 100 to a notch.
+                    MouseScrollDelta::PixelDelta(position) => position.y / 100.0,
+                };
+                let cursor = self.cursor;
+                self.move_camera(
+                    |camera| camera.zoom_at(cursor, ZOOM_STEP.powf(notches)),
+                    false,
+                );
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.renderer
+            .render(&self.surface, &self.device, &self.queue)
+    }
+}
+
+/// Cells on the straight line from `from` to `to`, both included.
+fn line(from: (i64, i64), to: (i64, i64)) -> impl Iterator<Item = (i64, i64)> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let steps = dx.abs().max(dy.abs()).max(1);
+    (0..=steps).map(move |i| {
+        (
+            from.0 + (dx as f64 * i as f64 / steps as f64).round() as i64,
+            from.1 + (dy as f64 * i as f64 / steps as f64).round() as i64,
+        )
+    })
+}
+
+/// Opens the window and runs the simulation in it until it's closed.
+pub async fn run(args: cli::Args, setup: cli::Setup) {
+    let event_loop = EventLoop::new();
+    let mut window_builder = WindowBuilder::new().with_title("Conway's Game of Life");
+    if let (Some(width), Some(height)) = (args.window_width, args.window_height) {
+        window_builder = window_builder.with_inner_size(PhysicalSize::new(width, height));
+    }
+    if args.fullscreen {
+        window_builder = window_builder.with_fullscreen(Some(Fullscreen::Borderless(None)));
+    }
+    let window = window_builder.build(&event_loop).unwrap();
+    let mut state = State::new(window, args, setup).await;
+    event_loop.run(move |event, _, control_flow| {
+        match event {
+            Event::WindowEvent {
+                ref event,
+                window_id,
+            } if window_id == state.window().id() && !state.input(event) => match event {
+                WindowEvent::CloseRequested
+                | WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                WindowEvent::Resized(physical_size) => {
+                    state.resize(*physical_size);
+                }
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    state.resize(**new_inner_size);
+                }
+                _ => {}
+            },
+            Event::RedrawRequested(window_id) if window_id == state.window().id() => {
+                pollster::block_on(state.update());
+                match state.render() {
+                    Ok(_) => {}
+                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                        state.resize(state.window_size)
+                    }
+                    Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
+                    Err(wgpu::SurfaceError::Timeout) => log::warn!("Surface timeout"),
+                }
+            }
+            Event::MainEventsCleared => {
+                // RedrawRequested will only trigger once, unless we manually
+                // request it. Waiting out --frame-delay here rather than
+                // sleeping keeps the window responsive in the meantime.
+                let next_frame = state.next_frame();
+                if Instant::now() >= next_frame {
+                    state.window().request_redraw();
+                    control_flow.set_poll();
+                } else {
+                    control_flow.set_wait_until(next_frame);
+                }
+            }
+            _ => {}
+        }
+    });
+}
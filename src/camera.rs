@@ -0,0 +1,197 @@
+use bytemuck::{Pod, Zeroable};
+
+/// Closest and furthest the camera zooms, in cells per screen pixel.
+const SCALE_RANGE: (f64, f64) = (1.0 / 64.0, 64.0);
+
+/// Which part of the grid the renderer shows, and how large.
+///
+/// Positions are in cells, with `x` to the right and `y` down from the top
+/// left of the grid, and screen positions in physical pixels from the top
+/// left of the viewport. The grid is a torus, so panning past an edge wraps
+/// around rather than stopping.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    /// Cell in the middle of the viewport.
+    centre: (f64, f64),
+    /// Cells per screen pixel, less than one when zoomed in.
+    scale: f64,
+    grid: (u32, u32),
+    viewport: (u32, u32),
+}
+
+/// `Camera` as laid out for `shaders/shader.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct CameraUniform {
+    centre: [f32; 2],
+    viewport: [f32; 2],
+    grid: [f32; 2],
+    scale: f32,
+    _padding: f32,
+}
+
+impl Camera {
+    /// The whole grid in a `viewport` sized view, keeping its aspect ratio.
+    pub fn fit(grid: (u32, u32), viewport: (u32, u32)) -> Self {
+        let mut camera = Self {
+            centre: (0.0, 0.0),
+            scale: 1.0,
+            grid,
+            viewport,
+        };
+        camera.fit_grid();
+        camera
+    }
+
+    /// Zooms out or in until the whole grid just fits, centred.
+    pub fn fit_grid(&mut self) {
+        self.centre = (self.grid.0 as f64 / 2.0, self.grid.1 as f64 / 2.0);
+        self.scale = (self.grid.0 as f64 / self.viewport.0.max(1) as f64)
+            .max(self.grid.1 as f64 / self.viewport.1.max(1) as f64);
+    }
+
+    /// One cell per screen pixel, keeping the cell in the middle where it is.
+    pub fn one_to_one(&mut self) {
+        self.scale = 1.0;
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn set_viewport(&mut self, viewport: (u32, u32)) {
+        self.viewport = viewport;
+    }
+
+    pub fn set_grid(&mut self, grid: (u32, u32)) {
+        self.grid = grid;
+        self.wrap_centre();
+    }
+
+    /// The cell under a screen position, not wrapped onto the grid.
+    pub fn screen_to_cell(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            self.centre.0 + (x - self.viewport.0 as f64 / 2.0) * self.scale,
+            self.centre.1 + (y - self.viewport.1 as f64 / 2.0) * self.scale,
+        )
+    }
+
+    /// Moves the view by a distance in screen pixels, so dragging by
+    /// `delta` moves the grid along with the cursor.
+    pub fn pan(&mut self, (dx, dy): (f64, f64)) {
+        self.centre.0 -= dx * self.scale;
+        self.centre.1 -= dy * self.scale;
+        self.wrap_centre();
+    }
+
+    /// Zooms in by `factor` (out when less than one), keeping the cell under
+    /// the screen position `at` in place.
+    pub fn zoom_at(&mut self, at: (f64, f64), factor: f64) {
+        let cell = self.screen_to_cell(at);
+        self.scale = (self.scale / factor).clamp(SCALE_RANGE.0, SCALE_RANGE.1);
+        let moved = self.screen_to_cell(at);
+        self.centre.0 += cell.0 - moved.0;
+        self.centre.1 += cell.1 - moved.1;
+        self.wrap_centre();
+    }
+
+    /// Keeps the centre on the grid, so panning around the torus forever
+    /// doesn't lose precision.
+    fn wrap_centre(&mut self) {
+        self.centre.0 = self.centre.0.rem_euclid(self.grid.0 as f64);
+        self.centre.1 = self.centre.1.rem_euclid(self.grid.1 as f64);
+    }
+
+    pub fn uniform(&self) -> CameraUniform {
+        CameraUniform {
+            centre: [self.centre.0 as f32, self.centre.1 as f32],
+            viewport: [self.viewport.0 as f32, self.viewport.1 as f32],
+            grid: [self.grid.0 as f32, self.grid.1 as f32],
+            scale: self.scale as f32,
+            _padding: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: (f64, f64), b: (f64, f64)) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    /// `cell` wrapped onto a `grid` sized torus.
+    fn wrapped(cell: (f64, f64), grid: (u32, u32)) -> (f64, f64) {
+        (
+            cell.0.rem_euclid(grid.0 as f64),
+            cell.1.rem_euclid(grid.1 as f64),
+        )
+    }
+
+    #[test]
+    fn fit_shows_the_whole_grid() {
+        // Twice as wide as the viewport, the height has room to spare.
+        let camera = Camera::fit((200, 50), (100, 100));
+        assert_eq!(camera.scale(), 2.0);
+        assert_near(camera.screen_to_cell((50.0, 50.0)), (100.0, 25.0));
+        assert_near(camera.screen_to_cell((0.0, 0.0)), (0.0, -75.0));
+        assert_near(camera.screen_to_cell((100.0, 100.0)), (200.0, 125.0));
+    }
+
+    #[test]
+    fn one_to_one_keeps_the_middle() {
+        let mut camera = Camera::fit((400, 400), (100, 100));
+        camera.pan((10.0, 0.0));
+        let middle = camera.screen_to_cell((50.0, 50.0));
+        camera.one_to_one();
+        assert_eq!(camera.scale(), 1.0);
+        assert_near(camera.screen_to_cell((50.0, 50.0)), middle);
+        assert_near(
+            camera.screen_to_cell((51.0, 50.0)),
+            (middle.0 + 1.0, middle.1),
+        );
+    }
+
+    #[test]
+    fn zoom_keeps_the_cell_under_the_cursor() {
+        let mut camera = Camera::fit((100, 100), (100, 100));
+        let at = (20.0, 70.0);
+        let cell = camera.screen_to_cell(at);
+        camera.zoom_at(at, 4.0);
+        assert_eq!(camera.scale(), 0.25);
+        assert_near(camera.screen_to_cell(at), cell);
+        camera.zoom_at(at, 0.1);
+        assert_eq!(camera.scale(), 2.5);
+        assert_near(wrapped(camera.screen_to_cell(at), (100, 100)), cell);
+        // Clamped to the zoom range.
+        camera.zoom_at(at, 1e-6);
+        assert_eq!(camera.scale(), SCALE_RANGE.1);
+    }
+
+    #[test]
+    fn pan_moves_the_grid_with_the_cursor() {
+        let mut camera = Camera::fit((100, 100), (100, 100));
+        camera.zoom_at((50.0, 50.0), 2.0);
+        let cell = camera.screen_to_cell((30.0, 30.0));
+        camera.pan((10.0, -20.0));
+        assert_near(camera.screen_to_cell((40.0, 10.0)), cell);
+    }
+
+    #[test]
+    fn wraps_around_the_grid() {
+        let mut camera = Camera::fit((100, 50), (100, 50));
+        // Past the left and top edges back onto the right and bottom.
+        camera.pan((80.0, 40.0));
+        assert_near(camera.screen_to_cell((50.0, 25.0)), (70.0, 35.0));
+        // Whole laps end up where they started.
+        camera.pan((-1000.0, 500.0));
+        assert_near(camera.screen_to_cell((50.0, 25.0)), (70.0, 35.0));
+        // Shrinking the grid wraps the centre onto it.
+        camera.set_grid((60, 20));
+        assert_near(camera.screen_to_cell((50.0, 25.0)), (10.0, 15.0));
+    }
+}
@@ -0,0 +1,673 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
+
+use conway::presets::Presets;
+use conway::{
+    gpu, grid, image, life, seed, CpuLife, Grid, Image, ImageError, Library, Pattern, Rule,
+    Simulation, Snapshot,
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "conway", about = "GPU accelerated cellular automata")]
+pub struct Args {
+    /// Width of the simulation grid in cells
+    #[arg(long, default_value_t = 2880, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: u32,
+
+    /// Height of the simulation grid in cells
+    #[arg(long, default_value_t = 1800, value_parser = clap::value_parser!(u32).range(1..))]
+    pub height: u32,
+
+    /// Value given to a newly born cell, dead cells count down from it to zero.
+    /// Ignored when the rule gives a number of states
+    #[arg(long, default_value_t = 1000, value_parser = clap::value_parser!(u32).range(1..))]
+    pub lifetime: u32,
+
+    /// Rulestring such as B3/S23, 23/3 or the Generations form B2/S/C3.
+    /// Defaults to the rule of --pattern, or B3/S23
+    #[arg(long)]
+    pub rule: Option<Rule>,
+
+    /// Start with a named rule instead of --rule, see --list-presets
+    #[arg(long, conflicts_with = "rule")]
+    pub preset: Option<String>,
+
+    /// File of extra presets, one `name = rule [lifetime]` per line
+    #[arg(long = "presets", value_name = "FILE")]
+    pub preset_files: Vec<PathBuf>,
+
+    /// Pattern file to add to the window's stamp library, may be repeated
+    #[arg(long = "stamp", value_name = "FILE")]
+    pub stamp_files: Vec<PathBuf>,
+
+    /// Print the available presets and exit
+    #[arg(long)]
+    pub list_presets: bool,
+
+    /// How the grid is filled at startup
+    #[arg(long, value_enum, default_value_t = Generator::Random)]
+    pub generator: Generator,
+
+    /// Seed for the random soup, a random one (printed at startup) by default
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Chance of each cell in the random soup being alive
+    #[arg(long, default_value_t = 0.5, value_parser = parse_fraction)]
+    pub density: f64,
+
+    /// Only fill a square of this side in the middle of the grid with soup
+    #[arg(long, value_name = "SIDE", value_parser = clap::value_parser!(u32).range(1..))]
+    pub soup_size: Option<u32>,
+
+    /// Symmetry of the random soup
+    #[arg(long, value_enum, default_value_t = Symmetry::None)]
+    pub symmetry: Symmetry,
+
+    /// Start live soup cells part way through their lifetime at random
+    #[arg(long)]
+    pub random_lifetimes: bool,
+
+    /// Pattern file to start from instead of --generator (.rle, .cells or .lif)
+    #[arg(long, value_name = "FILE")]
+    pub pattern: Option<PathBuf>,
+
+    /// Where to place the top left corner of --pattern, centred by default
+    #[arg(long, value_name = "X,Y", value_parser = parse_offset, requires = "pattern")]
+    pub offset: Option<(i64, i64)>,
+
+    /// Image to seed the grid from instead of --generator (.png or .ppm)
+    #[arg(long, value_name = "FILE", conflicts_with = "pattern")]
+    pub image: Option<PathBuf>,
+
+    /// How --image brightness becomes cell values
+    #[arg(long, value_enum, default_value_t = ImageMode::Threshold, requires = "image")]
+    pub image_mode: ImageMode,
+
+    /// Luminance between 0 and 1 from which --image pixels are alive
+    #[arg(long, default_value_t = 0.5, value_parser = parse_fraction, requires = "image")]
+    pub threshold: f64,
+
+    /// How --image is fitted to the grid
+    #[arg(long, value_enum, default_value_t = ImageFit::Scale, requires = "image")]
+    pub image_fit: ImageFit,
+
+    /// Snapshot to resume from, replacing the size, rule and grid arguments
+    #[arg(long, value_name = "FILE", conflicts_with_all = [
+        "width", "height", "lifetime", "rule", "preset", "generator", "pattern", "image",
+    ])]
+    pub load_state: Option<PathBuf>,
+
+    /// Where F5 and F9 save and load snapshots in the window, and where
+    /// headless mode saves the last generation
+    #[arg(long, value_name = "FILE")]
+    pub save_state: Option<PathBuf>,
+
+    /// Also save the snapshot every this many generations in headless mode
+    #[arg(long, requires_all = ["headless", "save_state"], value_parser = clap::value_parser!(u64).range(1..))]
+    pub save_every: Option<u64>,
+
+    /// Minimum milliseconds between frames drawn in the window
+    #[arg(long, default_value_t = 0)]
+    pub frame_delay: u32,
+
+    /// Generations per second in the window, independent of the frame rate.
+    /// Adjust with + and -
+    #[arg(long, default_value_t = 60.0, value_parser = parse_rate)]
+    pub rate: f64,
+
+    /// Initial window width in pixels
+    #[arg(long, requires = "window_height")]
+    pub window_width: Option<u32>,
+
+    /// Initial window height in pixels
+    #[arg(long, requires = "window_width")]
+    pub window_height: Option<u32>,
+
+    /// Start the window paused, Space resumes
+    #[arg(long)]
+    pub paused: bool,
+
+    /// Generations the N key advances by
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub step_size: u32,
+
+    /// Generations between the checkpoints that rewinding with , and B
+    /// replays from
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    pub rewind_every: u64,
+
+    /// Checkpoints and undo steps kept in the window, each costing 4 bytes
+    /// per cell
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    pub history: u64,
+
+    /// Open the window in borderless fullscreen
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Resize the grid along with the window, keeping the cells the size
+    /// they're shown at. Toggle with W
+    #[arg(long, conflicts_with = "headless")]
+    pub follow_window: bool,
+
+    /// What happens to the cells when the grid is resized in the window.
+    /// Cycle with M
+    #[arg(long, value_enum, default_value_t = ResizeMode::Centre)]
+    pub resize_mode: ResizeMode,
+
+    /// Run without a window for --generations generations and exit
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Number of generations to run in headless mode
+    #[arg(long, default_value_t = 1000, requires = "headless")]
+    pub generations: u64,
+
+    /// Print the population every this many generations in headless mode
+    #[arg(long, requires = "headless", value_parser = clap::value_parser!(u64).range(1..))]
+    pub report_every: Option<u64>,
+
+    /// Pattern file (.rle, .cells or .lif) to save the last generation to in
+    /// headless mode
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    pub output: Option<PathBuf>,
+
+    /// PNG to save the last generation to in headless mode
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    pub png: Option<PathBuf>,
+
+    /// Record frames in headless mode, into a directory of numbered PNGs or
+    /// a .y4m video
+    #[arg(long, value_name = "PATH", requires = "headless")]
+    pub record: Option<PathBuf>,
+
+    /// Capture a frame every this many generations
+    #[arg(long, default_value_t = 1, requires = "record", value_parser = clap::value_parser!(u64).range(1..))]
+    pub record_every: u64,
+
+    /// Stop recording after this many frames
+    #[arg(long, requires = "record", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_frames: Option<u64>,
+
+    /// Size of recorded frames, the grid size by default
+    #[arg(long, value_name = "WxH", value_parser = parse_size, requires = "record")]
+    pub frame_size: Option<(u32, u32)>,
+
+    /// Frame rate written into .y4m recordings
+    #[arg(long, default_value_t = 30, requires = "record", value_parser = clap::value_parser!(u32).range(1..))]
+    pub fps: u32,
+
+    /// Animated GIF of a range of generations to save in headless mode
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    pub gif: Option<PathBuf>,
+
+    /// Generation of the first GIF frame, the starting one by default
+    #[arg(long, value_name = "GENERATION", requires = "gif")]
+    pub gif_start: Option<u64>,
+
+    /// Number of GIF frames
+    #[arg(long, default_value_t = 50, requires = "gif", value_parser = clap::value_parser!(u64).range(1..))]
+    pub gif_frames: u64,
+
+    /// Generations between GIF frames
+    #[arg(long, default_value_t = 1, requires = "gif", value_parser = clap::value_parser!(u64).range(1..))]
+    pub gif_every: u64,
+
+    /// Milliseconds each GIF frame is shown for
+    #[arg(long, default_value_t = 100, requires = "gif")]
+    pub gif_delay: u32,
+
+    /// Region of the grid exported to PNGs and GIFs, the whole grid by default
+    #[arg(long, value_name = "X,Y,WxH", value_parser = parse_crop)]
+    pub crop: Option<(i64, i64, u32, u32)>,
+
+    /// Pixels per cell in exported PNGs and GIFs
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=64))]
+    pub scale: u32,
+
+    /// How exported images colour the cells
+    #[arg(long, value_enum, default_value_t = Colouring::Hsv)]
+    pub colouring: Colouring,
+
+    /// Where the simulation itself runs
+    #[arg(long, value_enum, default_value_t = Engine::Gpu)]
+    pub engine: Engine,
+
+    /// Graphics API to run on
+    #[arg(long, value_enum, default_value_t = Backend::Auto)]
+    pub backend: Backend,
+
+    /// Use a software adapter (lavapipe, WARP, ...) instead of a GPU
+    #[arg(long)]
+    pub software: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Engine {
+    /// Compute shader on the GPU
+    Gpu,
+    /// Multithreaded reference implementation, needs no GPU in headless mode
+    Cpu,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Backend {
+    /// Whatever wgpu finds first, or the WGPU_BACKEND environment variable
+    Auto,
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+}
+
+impl Backend {
+    pub fn backends(self) -> wgpu::Backends {
+        match self {
+            Backend::Auto => {
+                wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all)
+            }
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Colouring {
+    /// Grey, from black for empty cells to white for live ones
+    Raw,
+    /// The same colours as the window
+    Hsv,
+}
+
+impl Colouring {
+    pub fn colouring(self) -> conway::Colouring {
+        match self {
+            Colouring::Raw => conway::Colouring::Raw,
+            Colouring::Hsv => conway::Colouring::Hsv,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ResizeMode {
+    /// Keep the top left corner, cutting off or adding cells on the right and
+    /// bottom
+    Crop,
+    /// Keep the middle, cutting off or adding cells on every side
+    Centre,
+    /// Stretch or squash the cells to the new size
+    Scale,
+}
+
+impl ResizeMode {
+    pub fn resize(self) -> conway::Resize {
+        match self {
+            ResizeMode::Crop => conway::Resize::Crop,
+            ResizeMode::Centre => conway::Resize::Centre,
+            ResizeMode::Scale => conway::Resize::Scale,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Generator {
+    /// Random soup, see --seed, --density, --soup-size and --symmetry
+    Random,
+    /// A single live cell in the centre of the grid
+    Centre,
+    /// No live cells at all
+    Empty,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Symmetry {
+    None,
+    /// Unchanged by a half turn
+    C2,
+    /// Unchanged by a quarter turn, needs a square soup
+    C4,
+    /// Unchanged by quarter turns and reflections, needs a square soup
+    D8,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ImageMode {
+    /// Pixels at least --threshold bright are alive, the rest empty
+    Threshold,
+    /// Brighter pixels are further from decaying away, white ones alive
+    Lifetime,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ImageFit {
+    /// Stretch the image over the whole grid
+    Scale,
+    /// Repeat the image at its own size
+    Tile,
+}
+
+/// Everything the simulation starts from, resolved from the arguments.
+pub struct Setup {
+    pub rule: Rule,
+    pub params: life::Params,
+    pub data: Vec<u32>,
+    pub generation: u64,
+    /// The random soup the grid was filled with, if it was.
+    pub soup: Option<seed::Soup>,
+    pub presets: Presets,
+    pub preset: Option<usize>,
+    /// Patterns to stamp in the window.
+    pub library: Library,
+}
+
+impl Args {
+    /// Loads the presets, stamps, pattern, image or snapshot and works out the
+    /// starting rule and grid, exiting with a usage error if any of it fails.
+    pub fn setup(&self) -> Setup {
+        if self.width as u64 * self.height as u64 > grid::MAX_CELLS {
+            exit_with(
+                ErrorKind::ValueValidation,
+                format!(
+                    "a {}x{} grid is more than the limit of {} cells",
+                    self.width,
+                    self.height,
+                    grid::MAX_CELLS
+                ),
+            );
+        }
+        let presets = self.presets();
+        let preset = self.preset.as_ref().map(|name| {
+            presets.position(name).unwrap_or_else(|| {
+                exit_with(
+                    ErrorKind::InvalidValue,
+                    format!("unknown preset '{name}', see --list-presets"),
+                )
+            })
+        });
+        let mut library = Library::builtin();
+        for path in &self.stamp_files {
+            if let Err(e) = library.load_file(path) {
+                exit_with(
+                    ErrorKind::Io,
+                    format!("failed to load {}: {e}", path.display()),
+                );
+            }
+        }
+        if let Some(path) = &self.load_state {
+            let snapshot = Snapshot::load(path).unwrap_or_else(|e| {
+                exit_with(
+                    ErrorKind::Io,
+                    format!("failed to load {}: {e}", path.display()),
+                )
+            });
+            return Setup {
+                rule: snapshot.rule(),
+                params: snapshot.params,
+                data: snapshot.cells,
+                generation: snapshot.generation,
+                soup: None,
+                presets,
+                preset: None,
+                library,
+            };
+        }
+
+        let pattern = self.pattern.as_ref().map(|path| {
+            Pattern::load(path).unwrap_or_else(|e| {
+                exit_with(
+                    ErrorKind::Io,
+                    format!("failed to load {}: {e}", path.display()),
+                )
+            })
+        });
+
+        // An explicit preset or rule wins over the one in the pattern file.
+        let pattern_rule =
+            pattern
+                .as_ref()
+                .and_then(|p| p.rule.as_ref())
+                .and_then(|rule| match rule.parse::<Rule>() {
+                    Ok(rule) => Some(rule),
+                    Err(e) => {
+                        log::warn!("ignoring the pattern's rule '{rule}': {e}");
+                        None
+                    }
+                });
+        let (rule, lifetime) = match preset {
+            Some(i) => (
+                presets[i].rule,
+                presets[i].lifetime.unwrap_or(self.lifetime),
+            ),
+            None => (
+                self.rule.or(pattern_rule).unwrap_or(Rule::LIFE),
+                self.lifetime,
+            ),
+        };
+        let params = rule.params(self.width, self.height, lifetime);
+
+        let image = self.image.as_ref().map(|path| {
+            Image::load(path).unwrap_or_else(|e| {
+                exit_with(
+                    ErrorKind::Io,
+                    format!("failed to load {}: {e}", path.display()),
+                )
+            })
+        });
+        let soup = match (&pattern, &image, self.generator) {
+            (None, None, Generator::Random) => Some(self.soup()),
+            _ => None,
+        };
+        let data = match (&pattern, &image) {
+            (Some(pattern), _) => {
+                let mut grid = Grid::new(self.width, self.height);
+                let (x, y) = self.offset.unwrap_or((
+                    (self.width as i64 - pattern.width() as i64) / 2,
+                    (self.height as i64 - pattern.height() as i64) / 2,
+                ));
+                pattern.place(&mut grid, x, y, params.lifetime());
+                grid.into_cells()
+            }
+            (None, Some(image)) => {
+                let brightness = match self.image_mode {
+                    ImageMode::Threshold => seed::Brightness::Threshold(self.threshold as f32),
+                    ImageMode::Lifetime => seed::Brightness::Lifetime,
+                };
+                let fit = match self.image_fit {
+                    ImageFit::Scale => seed::Fit::Scale,
+                    ImageFit::Tile => seed::Fit::Tile,
+                };
+                seed::from_image(
+                    image,
+                    self.width,
+                    self.height,
+                    params.lifetime(),
+                    brightness,
+                    fit,
+                )
+            }
+            (None, None) => self.generate(&params, soup.as_ref()),
+        };
+
+        Setup {
+            rule,
+            params,
+            data,
+            generation: 0,
+            soup,
+            presets,
+            preset,
+            library,
+        }
+    }
+
+    /// The builtin presets followed by those in the --presets files, exiting
+    /// with a usage error if a file fails to load.
+    pub fn presets(&self) -> Presets {
+        let mut presets = Presets::builtin();
+        for path in &self.preset_files {
+            if let Err(e) = presets.load_file(path) {
+                exit_with(
+                    ErrorKind::Io,
+                    format!("failed to load presets from {}: {e}", path.display()),
+                );
+            }
+        }
+        presets
+    }
+
+    /// Draws the current generation, or the --crop region of it, for
+    /// exporting. Fails if it would be more than `image::MAX_PIXELS`.
+    pub fn image(&self, life: &mut dyn Simulation) -> Result<Image, ImageError> {
+        // Checked first so a huge --crop fails before its region is copied.
+        self.image_size(&life.params())?;
+        let lifetime = life.params().lifetime();
+        let mut grid = life.read_grid();
+        if let Some((x, y, width, height)) = self.crop {
+            grid = grid.region(x, y, width, height);
+        }
+        Image::from_grid(&grid, lifetime, self.colouring.colouring(), self.scale)
+    }
+
+    /// Size in pixels of the images `image` draws.
+    pub fn image_size(&self, params: &life::Params) -> Result<(u32, u32), ImageError> {
+        let (width, height) = self
+            .crop
+            .map_or((params.width, params.height), |(_, _, w, h)| (w, h));
+        image::scaled_size(width, height, self.scale)
+    }
+
+    /// Snapshot file used by F5 and F9 in the window.
+    pub fn state_path(&self) -> PathBuf {
+        self.save_state
+            .clone()
+            .or_else(|| self.load_state.clone())
+            .unwrap_or_else(|| PathBuf::from("conway.snapshot"))
+    }
+
+    pub fn instance(&self) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: self.backend.backends(),
+            dx12_shader_compiler: wgpu::Dx12Compiler::default(),
+        })
+    }
+
+    /// Creates the simulation for --engine. The GPU engine needs a device, the
+    /// CPU engine only uses one for its output texture when given one.
+    pub fn simulation(
+        &self,
+        data: Vec<u32>,
+        params: life::Params,
+        gpu: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>,
+    ) -> Box<dyn Simulation> {
+        if let Some((device, _)) = &gpu {
+            if let Err(e) = gpu::check_grid_size(device, params.width, params.height) {
+                exit_with(ErrorKind::ValueValidation, e);
+            }
+        }
+        match (self.engine, gpu) {
+            (Engine::Gpu, Some((device, queue))) => {
+                Box::new(life::Life::new(device, queue, data, params))
+            }
+            (Engine::Gpu, None) => panic!("the GPU engine needs a device"),
+            (Engine::Cpu, Some((device, queue))) => {
+                Box::new(CpuLife::new(data, params).with_output(device, queue))
+            }
+            (Engine::Cpu, None) => Box::new(CpuLife::new(data, params)),
+        }
+    }
+
+    /// The random soup settings, with a fresh seed unless --seed was given.
+    pub fn soup(&self) -> seed::Soup {
+        let soup = seed::Soup {
+            seed: self.seed.unwrap_or_else(rand::random),
+            density: self.density,
+            size: self.soup_size,
+            symmetry: match self.symmetry {
+                Symmetry::None => seed::Symmetry::None,
+                Symmetry::C2 => seed::Symmetry::C2,
+                Symmetry::C4 => seed::Symmetry::C4,
+                Symmetry::D8 => seed::Symmetry::D8,
+            },
+            random_lifetimes: self.random_lifetimes,
+        };
+        let side = |n: u32| self.soup_size.map_or(n, |side| side.min(n));
+        if soup.symmetry.needs_square() && side(self.width) != side(self.height) {
+            exit_with(
+                ErrorKind::ArgumentConflict,
+                "this --symmetry needs a square soup, use --soup-size or a square grid",
+            );
+        }
+        soup
+    }
+
+    fn generate(&self, params: &life::Params, soup: Option<&seed::Soup>) -> Vec<u32> {
+        let lifetime = params.lifetime();
+        match self.generator {
+            Generator::Random => seed::soup(
+                self.width,
+                self.height,
+                lifetime,
+                soup.expect("random generator without soup settings"),
+            ),
+            Generator::Centre => seed::centre(self.width, self.height, lifetime),
+            Generator::Empty => seed::empty(self.width, self.height),
+        }
+    }
+}
+
+fn parse_offset(s: &str) -> Result<(i64, i64), String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected X,Y, found '{s}'"))?;
+    let parse = |n: &str| {
+        n.trim()
+            .parse::<i64>()
+            .map_err(|_| format!("'{n}' is not a whole number"))
+    };
+    Ok((parse(x)?, parse(y)?))
+}
+
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| format!("expected WxH, found '{s}'"))?;
+    let parse = |n: &str| match n.trim().parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("'{n}' is not a positive whole number")),
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+fn parse_crop(s: &str) -> Result<(i64, i64, u32, u32), String> {
+    let (offset, size) = s
+        .rsplit_once(',')
+        .ok_or_else(|| format!("expected X,Y,WxH, found '{s}'"))?;
+    let (x, y) = parse_offset(offset)?;
+    let (width, height) = parse_size(size)?;
+    Ok((x, y, width, height))
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err(format!("expected a positive number, found '{s}'")),
+    }
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("expected a number between 0 and 1, found '{s}'")),
+    }
+}
+
+fn exit_with(kind: ErrorKind, message: impl std::fmt::Display) -> ! {
+    Args::command().error(kind, message).exit()
+}
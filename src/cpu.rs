@@ -0,0 +1,344 @@
+use std::sync::Arc;
+use std::thread;
+
+use wgpu::{Device, Queue, Texture};
+
+use crate::gpu;
+use crate::grid::{Grid, Resize};
+use crate::life::Params;
+use crate::simulation::{self, Simulation};
+
+/// Reference implementation of `shaders/life.wgsl` that runs entirely on the
+/// CPU. Produces exactly the same generations as `Life`, so it doubles as a
+/// check on the GPU results and as a fallback when there is no usable adapter.
+pub struct CpuLife {
+    params: Params,
+    generation: u64,
+    cells: Vec<u32>,
+    next: Vec<u32>,
+    /// Cells the simulation started from, resized with the grid, for `reset`.
+    initial: Vec<u32>,
+    /// Lifetime `initial` was written for, it's rescaled to the current one
+    /// when resetting.
+    initial_lifetime: u32,
+    output: Option<Output>,
+}
+
+/// Texture the CPU generations are uploaded to when they're being rendered.
+struct Output {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    texture: Texture,
+}
+
+impl CpuLife {
+    pub fn new(data: Vec<u32>, params: Params) -> Self {
+        assert_eq!(data.len(), (params.width * params.height) as usize);
+        Self {
+            params,
+            generation: 0,
+            next: vec![0; data.len()],
+            initial: data.clone(),
+            initial_lifetime: params.lifetime(),
+            cells: data,
+            output: None,
+        }
+    }
+
+    /// Uploads every generation to an output texture on `device`, so the
+    /// normal renderer can draw a CPU simulation.
+    pub fn with_output(mut self, device: Arc<Device>, queue: Arc<Queue>) -> Self {
+        let texture = gpu::create_output_texture(&device, &self.params);
+        self.output = Some(Output {
+            device,
+            queue,
+            texture,
+        });
+        self.write_texture();
+        self
+    }
+
+    /// Fills the output texture the same way the compute shader does.
+    fn write_texture(&self) {
+        let Some(output) = &self.output else {
+            return;
+        };
+        let lifetime = self.params.lifetime() as f32;
+        let texels = self
+            .cells
+            .iter()
+            .flat_map(|&c| {
+                let v = c as f32 / lifetime;
+                [v, v, v, 1.0]
+            })
+            .collect::<Vec<f32>>();
+        output.queue.write_texture(
+            output.texture.as_image_copy(),
+            bytemuck::cast_slice(&texels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(self.params.width * 16),
+                rows_per_image: None,
+            },
+            output.texture.size(),
+        );
+    }
+
+    fn step_once(&mut self) {
+        let params = &self.params;
+        let rules = (params.alive_rules(), params.dead_rules());
+        let rules = &rules;
+        let width = params.width as usize;
+        let cells = &self.cells;
+
+        // Rows are independent of each other within a generation, so split
+        // them evenly between the available cores.
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let rows_per_thread = (params.height as usize).div_ceil(threads);
+        thread::scope(|scope| {
+            for (i, chunk) in self.next.chunks_mut(rows_per_thread * width).enumerate() {
+                scope.spawn(move || {
+                    for (j, row) in chunk.chunks_mut(width).enumerate() {
+                        step_row(params, rules, cells, (i * rows_per_thread + j) as u32, row);
+                    }
+                });
+            }
+        });
+
+        std::mem::swap(&mut self.cells, &mut self.next);
+    }
+}
+
+impl Simulation for CpuLife {
+    fn params(&self) -> Params {
+        self.params
+    }
+
+    fn set_params(&mut self, params: Params) {
+        assert_eq!(
+            (params.width, params.height),
+            (self.params.width, self.params.height),
+            "set_params can't change the grid size, use resize"
+        );
+        self.params = params;
+        self.write_texture();
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    fn step_n(&mut self, n: u32) {
+        for _ in 0..n {
+            self.step_once();
+        }
+        self.generation += n as u64;
+        // Only the last generation is ever visible.
+        self.write_texture();
+    }
+
+    fn read_cells(&mut self) -> Vec<u32> {
+        self.cells.clone()
+    }
+
+    fn write_cells(&mut self, cells: &[u32]) {
+        assert_eq!(cells.len(), self.cells.len());
+        self.cells.copy_from_slice(cells);
+        self.write_texture();
+    }
+
+    fn set_cells(&mut self, positions: &[(u32, u32)], value: u32) {
+        for &(x, y) in positions {
+            assert!(x < self.params.width && y < self.params.height);
+            self.cells[(y * self.params.width + x) as usize] = value;
+        }
+        self.write_texture();
+    }
+
+    fn reset(&mut self) {
+        let (from, to) = (self.initial_lifetime, self.params.lifetime());
+        for (cell, &initial) in self.cells.iter_mut().zip(&self.initial) {
+            *cell = simulation::rescale(initial, from, to);
+        }
+        self.generation = 0;
+        self.write_texture();
+    }
+
+    fn resize(&mut self, width: u32, height: u32, cells: Vec<u32>, resize: Resize) {
+        assert_eq!(cells.len(), (width * height) as usize);
+        let initial = std::mem::take(&mut self.initial);
+        self.initial = Grid::from_cells(self.params.width, self.params.height, initial)
+            .resized(width, height, resize)
+            .into_cells();
+        self.params.width = width;
+        self.params.height = height;
+        self.next = vec![0; cells.len()];
+        self.cells = cells;
+        if let Some(output) = &mut self.output {
+            output.texture = gpu::create_output_texture(&output.device, &self.params);
+        }
+        self.write_texture();
+    }
+
+    fn output_texture(&self) -> Option<&Texture> {
+        self.output.as_ref().map(|output| &output.texture)
+    }
+}
+
+/// `modulus` in life.wgsl, wraps coordinates around the torus.
+fn modulus(a: i32, b: i32) -> i32 {
+    ((a % b) + b) % b
+}
+
+/// The body of `main` in life.wgsl for every cell in row `y`. `rules` holds
+/// the alive and dead rule tables.
+fn step_row(params: &Params, rules: &([u32; 9], [u32; 9]), cells: &[u32], y: u32, out: &mut [u32]) {
+    let lifetime = params.lifetime();
+    let width = params.width as i32;
+    let height = params.height as i32;
+    let row = |y_mod: i32| {
+        let y = modulus(y as i32 + y_mod, height) as usize;
+        &cells[y * width as usize..(y + 1) * width as usize]
+    };
+    let rows = [row(-1), row(0), row(1)];
+
+    for (x, new_value) in out.iter_mut().enumerate() {
+        let columns = [
+            modulus(x as i32 - 1, width) as usize,
+            x,
+            modulus(x as i32 + 1, width) as usize,
+        ];
+        let mut total = 0;
+        for (y_mod, row) in rows.iter().enumerate() {
+            for (x_mod, &column) in columns.iter().enumerate() {
+                if (x_mod, y_mod) != (1, 1) && row[column] == lifetime {
+                    total += 1;
+                }
+            }
+        }
+
+        let old_value = rows[1][x];
+        let is_alive = if old_value == lifetime {
+            rules.0[total] == 1
+        } else {
+            rules.1[total] == 1
+        };
+
+        *new_value = if is_alive {
+            lifetime
+        } else {
+            old_value.saturating_sub(1)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rule;
+
+    /// A `width` by `height` Life simulation with `alive` cells set to
+    /// `lifetime`.
+    fn life(width: u32, height: u32, lifetime: u32, alive: &[(u32, u32)]) -> CpuLife {
+        let mut data = vec![0; (width * height) as usize];
+        for &(x, y) in alive {
+            data[(y * width + x) as usize] = lifetime;
+        }
+        CpuLife::new(data, Rule::LIFE.params(width, height, lifetime))
+    }
+
+    fn alive(life: &mut CpuLife) -> Vec<(u32, u32)> {
+        let width = life.params().width;
+        let lifetime = life.params().lifetime();
+        let cells = life.read_cells();
+        (0..cells.len() as u32)
+            .filter(|&i| cells[i as usize] == lifetime)
+            .map(|i| (i % width, i / width))
+            .collect()
+    }
+
+    #[test]
+    fn blinker_oscillates() {
+        let horizontal = [(1, 2), (2, 2), (3, 2)];
+        let vertical = [(2, 1), (2, 2), (2, 3)];
+        let mut life = life(5, 5, 1, &horizontal);
+        life.step();
+        assert_eq!(alive(&mut life), vertical);
+        life.step();
+        assert_eq!(alive(&mut life), horizontal);
+        assert_eq!(life.generation(), 2);
+    }
+
+    #[test]
+    fn glider_translates() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let mut life = life(8, 8, 1, &glider);
+        life.step_n(4);
+        let moved = glider.map(|(x, y)| (x + 1, y + 1));
+        let mut expected = moved.to_vec();
+        expected.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(alive(&mut life), expected);
+    }
+
+    #[test]
+    fn dead_cells_decay() {
+        let mut life = life(5, 5, 3, &[(2, 2)]);
+        let mut centre = Vec::new();
+        for _ in 0..4 {
+            life.step();
+            centre.push(life.read_cells()[12]);
+        }
+        // A lone cell dies and counts down from the lifetime to zero.
+        assert_eq!(centre, [2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn decaying_cells_can_be_born_again() {
+        // The ends of the blinker die and start decaying, then are born
+        // again a generation later while the other ends decay.
+        let mut life = life(5, 5, 3, &[(1, 2), (2, 2), (3, 2)]);
+        life.step();
+        let cells = life.read_cells();
+        assert_eq!((cells[11], cells[12], cells[13]), (2, 3, 2));
+        life.step();
+        assert_eq!(alive(&mut life), [(1, 2), (2, 2), (3, 2)]);
+        assert_eq!(life.read_cells()[7], 2);
+    }
+
+    #[test]
+    fn reset_uses_the_current_lifetime() {
+        let mut data = vec![0; 25];
+        data[..3].copy_from_slice(&[4, 2, 1]);
+        let mut life = CpuLife::new(data, Rule::LIFE.params(5, 5, 4));
+        life.step();
+        life.set_rule(&Rule::LIFE, 8);
+        life.reset();
+        assert_eq!(life.read_cells()[..3], [8, 4, 2]);
+        assert_eq!(life.params().lifetime(), 8);
+    }
+
+    #[test]
+    fn reset_restores_the_first_generation() {
+        let mut life = life(5, 5, 2, &[(1, 2), (2, 2), (3, 2)]);
+        let initial = life.read_cells();
+        life.step_n(3);
+        life.reset();
+        assert_eq!(life.read_cells(), initial);
+        assert_eq!(life.generation(), 0);
+    }
+
+    #[test]
+    fn reset_after_resizing_restores_the_resized_start() {
+        let mut life = life(5, 5, 2, &[(1, 2), (2, 2), (3, 2)]);
+        let initial = life.read_grid();
+        life.step();
+        life.resize_to(7, 7, Resize::Centre);
+        life.reset();
+        assert_eq!(life.read_grid(), initial.resized(7, 7, Resize::Centre));
+        assert_eq!(alive(&mut life), [(2, 3), (3, 3), (4, 3)]);
+    }
+}
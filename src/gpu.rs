@@ -0,0 +1,76 @@
+use wgpu::{Adapter, Device, Instance, Queue, Surface, Texture};
+
+use crate::life::Params;
+
+/// Picks an adapter (compatible with `surface` when there is one) and opens a
+/// device on it. `force_fallback` asks for a software adapter such as
+/// lavapipe or WARP.
+pub async fn request_device(
+    instance: &Instance,
+    surface: Option<&Surface>,
+    force_fallback: bool,
+) -> (Adapter, Device, Queue) {
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: force_fallback,
+            compatible_surface: surface,
+        })
+        .await
+        .expect("no suitable GPU adapter found");
+    log::info!("using adapter {:?}", adapter.get_info());
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .unwrap();
+    (adapter, device, queue)
+}
+
+/// Checks that a `width` by `height` grid fits in `device`'s limits, both as
+/// the output texture and as a storage buffer of cells.
+pub fn check_grid_size(device: &Device, width: u32, height: u32) -> Result<(), String> {
+    let limits = device.limits();
+    let max_side = limits.max_texture_dimension_2d;
+    if width > max_side || height > max_side {
+        return Err(format!(
+            "{width}x{height} is larger than the GPU's limit of {max_side} cells a side"
+        ));
+    }
+    let max_cells = limits.max_storage_buffer_binding_size as u64 / 4;
+    if width as u64 * height as u64 > max_cells {
+        return Err(format!(
+            "{width}x{height} is more than the {max_cells} cells the GPU allows"
+        ));
+    }
+    Ok(())
+}
+
+/// Texture the compute shader colours each generation into and the renderer
+/// samples from. Shared between `Life` and `Renderer`, so it has to be created
+/// before either of them.
+pub fn create_output_texture(device: &Device, params: &Params) -> Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: params.width,
+            height: params.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        // COPY_DST lets the CPU engine upload its generations for rendering.
+        usage: wgpu::TextureUsages::STORAGE_BINDING
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST,
+        format: wgpu::TextureFormat::Rgba32Float,
+        view_formats: &[],
+    })
+}
@@ -0,0 +1,212 @@
+/// Most cells a grid may have, so that a cell's index always fits in a `u32`
+/// as it does on the GPU.
+pub const MAX_CELLS: u64 = u32::MAX as u64;
+
+/// A generation of cells held on the CPU, one `u32` per cell in row major
+/// order as in the GPU buffers. A cell equal to the lifetime is alive, lower
+/// values are dying and zero is dead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid {
+    width: u32,
+    height: u32,
+    cells: Vec<u32>,
+}
+
+/// How the cells are kept when a grid changes size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resize {
+    /// Keeps the top left corner in place, cutting off or adding empty cells
+    /// on the right and bottom.
+    Crop,
+    /// Keeps the middle in place, cutting off or adding empty cells evenly on
+    /// every side.
+    Centre,
+    /// Stretches or squashes the contents to the new size.
+    Scale,
+}
+
+impl Grid {
+    /// An empty grid.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![0; (width * height) as usize],
+        }
+    }
+
+    pub fn from_cells(width: u32, height: u32, cells: Vec<u32>) -> Self {
+        assert_eq!(
+            cells.len(),
+            (width * height) as usize,
+            "cells don't match a {width}x{height} grid"
+        );
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn cells(&self) -> &[u32] {
+        &self.cells
+    }
+
+    pub fn cells_mut(&mut self) -> &mut [u32] {
+        &mut self.cells
+    }
+
+    pub fn into_cells(self) -> Vec<u32> {
+        self.cells
+    }
+
+    fn index(&self, x: i64, y: i64) -> usize {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        y * self.width as usize + x
+    }
+
+    /// The cell at `x`, `y`, wrapping around the edges like the simulation.
+    pub fn get(&self, x: i64, y: i64) -> u32 {
+        self.cells[self.index(x, y)]
+    }
+
+    /// Sets the cell at `x`, `y`, wrapping around the edges like the
+    /// simulation.
+    pub fn set(&mut self, x: i64, y: i64, value: u32) {
+        let index = self.index(x, y);
+        self.cells[index] = value;
+    }
+
+    /// Copy of the `width` by `height` region with its top left corner at `x`,
+    /// `y`, wrapping around the edges.
+    pub fn region(&self, x: i64, y: i64, width: u32, height: u32) -> Grid {
+        let cells = (0..height as i64)
+            .flat_map(|dy| (0..width as i64).map(move |dx| (x + dx, y + dy)))
+            .map(|(x, y)| self.get(x, y))
+            .collect();
+        Grid::from_cells(width, height, cells)
+    }
+
+    /// Number of cells that are alive, i.e. equal to `lifetime`.
+    pub fn population(&self, lifetime: u32) -> usize {
+        self.cells.iter().filter(|&&c| c == lifetime).count()
+    }
+
+    /// This grid at `width` by `height`, keeping the cells as `resize` says.
+    pub fn resized(&self, width: u32, height: u32, resize: Resize) -> Grid {
+        let mut grid = Grid::new(width, height);
+        match resize {
+            Resize::Crop | Resize::Centre => {
+                let (dx, dy) = match resize {
+                    Resize::Crop => (0, 0),
+                    _ => (
+                        (width as i64 - self.width as i64) / 2,
+                        (height as i64 - self.height as i64) / 2,
+                    ),
+                };
+                for y in 0..height as i64 {
+                    for x in 0..width as i64 {
+                        let (sx, sy) = (x - dx, y - dy);
+                        if (0..self.width as i64).contains(&sx)
+                            && (0..self.height as i64).contains(&sy)
+                        {
+                            grid.set(x, y, self.get(sx, sy));
+                        }
+                    }
+                }
+            }
+            // Nearest neighbour, sampling the middle of each new cell.
+            Resize::Scale => {
+                for y in 0..height as u64 {
+                    for x in 0..width as u64 {
+                        let sx = (2 * x + 1) * self.width as u64 / (2 * width as u64);
+                        let sy = (2 * y + 1) * self.height as u64 / (2 * height as u64);
+                        grid.set(x as i64, y as i64, self.get(sx as i64, sy as i64));
+                    }
+                }
+            }
+        }
+        grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A grid whose cells count up from 1 in row major order.
+    fn numbered(width: u32, height: u32) -> Grid {
+        Grid::from_cells(width, height, (1..=width * height).collect())
+    }
+
+    #[test]
+    fn region_wraps_around_the_edges() {
+        let grid = numbered(3, 2);
+        assert_eq!(grid.region(1, 0, 2, 2).cells(), [2, 3, 5, 6]);
+        assert_eq!(grid.region(-1, 1, 3, 2).cells(), [6, 4, 5, 3, 1, 2]);
+        // Larger than the grid, repeating it.
+        assert_eq!(grid.region(0, 0, 4, 1).cells(), [1, 2, 3, 1]);
+    }
+
+    #[test]
+    fn crop_keeps_the_top_left_corner() {
+        let grid = numbered(3, 2);
+        assert_eq!(
+            grid.resized(4, 3, Resize::Crop).cells(),
+            [1, 2, 3, 0, 4, 5, 6, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(grid.resized(2, 1, Resize::Crop).cells(), [1, 2]);
+    }
+
+    #[test]
+    fn centre_keeps_the_middle() {
+        let grid = numbered(3, 3);
+        assert_eq!(
+            grid.resized(5, 5, Resize::Centre),
+            Grid::from_cells(
+                5,
+                5,
+                vec![
+                    0, 0, 0, 0, 0, //
+                    0, 1, 2, 3, 0, //
+                    0, 4, 5, 6, 0, //
+                    0, 7, 8, 9, 0, //
+                    0, 0, 0, 0, 0,
+                ]
+            )
+        );
+        assert_eq!(grid.resized(1, 1, Resize::Centre).cells(), [5]);
+        // An odd difference puts the extra row or column after.
+        assert_eq!(
+            grid.resized(4, 3, Resize::Centre).cells()[..4],
+            [1, 2, 3, 0]
+        );
+    }
+
+    #[test]
+    fn scale_stretches_and_squashes() {
+        let grid = numbered(2, 2);
+        assert_eq!(
+            grid.resized(4, 2, Resize::Scale).cells(),
+            [1, 1, 2, 2, 3, 3, 4, 4]
+        );
+        assert_eq!(
+            grid.resized(2, 4, Resize::Scale).cells(),
+            [1, 2, 1, 2, 3, 4, 3, 4]
+        );
+        assert_eq!(
+            numbered(4, 4).resized(2, 2, Resize::Scale).cells(),
+            [6, 8, 14, 16]
+        );
+        assert_eq!(grid.resized(2, 2, Resize::Scale), grid);
+    }
+}
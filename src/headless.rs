@@ -0,0 +1,213 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use conway::{gpu, Animation, Pattern, Recorder, Rule, Simulation, Snapshot};
+
+use crate::cli;
+
+/// Most generations recorded into one submission, so a long run doesn't build
+/// one enormous command buffer.
+const MAX_BATCH: u64 = 100;
+
+/// Runs the simulation without creating a window or surface, printing the
+/// population as it goes. The last generation is saved to `--output`,
+/// `--save-state` and `--png` if given, and frames captured to `--record` and
+/// `--gif`.
+pub async fn run(args: &cli::Args, setup: cli::Setup) {
+    // The CPU engine doesn't need a GPU at all without a window, unless it's
+    // being recorded.
+    let gpu = if args.engine == cli::Engine::Gpu || args.record.is_some() {
+        let instance = args.instance();
+        let (adapter, device, queue) = gpu::request_device(&instance, None, args.software).await;
+        println!("adapter: {}", adapter.get_info().name);
+        Some((Arc::new(device), Arc::new(queue)))
+    } else {
+        None
+    };
+    let mut life = args.simulation(setup.data, setup.params, gpu.clone());
+    life.set_generation(setup.generation);
+    let mut recorder = args.record.as_ref().map(|path| {
+        let (device, queue) = gpu.expect("recording needs a device");
+        let params = life.params();
+        let (width, height) = args.frame_size.unwrap_or((params.width, params.height));
+        let texture = life
+            .output_texture()
+            .expect("simulation has no output texture");
+        Recorder::new(device, queue, texture, width, height, path, args.fps).unwrap_or_else(|e| {
+            eprintln!("failed to record to {}: {e}", path.display());
+            std::process::exit(1)
+        })
+    });
+    record(args, &mut recorder);
+    let gif_start = args.gif_start.unwrap_or(setup.generation);
+    let mut animation = args.gif.as_ref().map(|path| {
+        let size = args.image_size(&life.params());
+        let animation =
+            size.and_then(|(width, height)| Animation::create(path, width, height, args.gif_delay));
+        animation.unwrap_or_else(|e| {
+            eprintln!("failed to create {}: {e}", path.display());
+            std::process::exit(1)
+        })
+    });
+    animate(args, gif_start, &mut animation, life.as_mut());
+
+    // --generations counts from wherever a resumed snapshot left off.
+    let end = setup.generation + args.generations;
+    let start = Instant::now();
+    while life.generation() < end {
+        let generation = life.generation();
+        let batch = (end - generation)
+            .min(until_multiple(args.report_every, generation))
+            .min(until_multiple(args.save_every, generation))
+            .min(until_multiple(
+                recorder.as_ref().map(|_| args.record_every),
+                generation,
+            ))
+            .min(match animation {
+                Some(_) if generation < gif_start => gif_start - generation,
+                Some(_) => args.gif_every - (generation - gif_start) % args.gif_every,
+                None => u64::MAX,
+            })
+            .min(MAX_BATCH);
+        life.step_n(batch as u32);
+        if reports(args, life.generation()) {
+            report(life.as_mut());
+        }
+        if let Some(path) = &args.save_state {
+            if saves(args, life.generation()) {
+                save_state(life.as_mut(), &setup.rule, path);
+            }
+        }
+        if life.generation() % args.record_every == 0 {
+            record(args, &mut recorder);
+        }
+        animate(args, gif_start, &mut animation, life.as_mut());
+    }
+    if !reports(args, life.generation()) {
+        report(life.as_mut());
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{} generations in {:.3}s ({:.1} generations/s)",
+        args.generations,
+        elapsed.as_secs_f64(),
+        args.generations as f64 / elapsed.as_secs_f64()
+    );
+
+    if let Some(recorder) = recorder {
+        finish(recorder);
+    }
+    if let Some(animation) = animation {
+        finish_gif(args, animation);
+    }
+
+    if let Some(path) = &args.output {
+        let params = life.params();
+        let grid = life.read_grid();
+        let mut pattern =
+            Pattern::from_grid(&grid, 0, 0, params.width, params.height, params.lifetime());
+        pattern.rule = Some(setup.rule.to_string());
+        match pattern.save(path) {
+            Ok(()) => println!("saved {}", path.display()),
+            Err(e) => log::error!("failed to save {}: {e}", path.display()),
+        }
+    }
+    if let Some(path) = &args.png {
+        match args
+            .image(life.as_mut())
+            .and_then(|image| image.save_png(path))
+        {
+            Ok(()) => println!("saved {}", path.display()),
+            Err(e) => log::error!("failed to save {}: {e}", path.display()),
+        }
+    }
+    if let Some(path) = &args.save_state {
+        if !saves(args, life.generation()) {
+            save_state(life.as_mut(), &setup.rule, path);
+        }
+    }
+}
+
+/// Generations left until the next multiple of `every`, if there is one.
+fn until_multiple(every: Option<u64>, generation: u64) -> u64 {
+    every.map_or(u64::MAX, |n| n - generation % n)
+}
+
+fn reports(args: &cli::Args, generation: u64) -> bool {
+    args.report_every.is_some_and(|n| generation % n == 0)
+}
+
+/// Captures a frame, and stops recording once --max-frames have been.
+fn record(args: &cli::Args, recorder: &mut Option<Recorder>) {
+    let Some(r) = recorder else {
+        return;
+    };
+    if let Err(e) = r.capture() {
+        log::error!("failed to record frame {}: {e}", r.frames());
+    }
+    if args.max_frames.is_some_and(|max| r.frames() >= max) {
+        finish(recorder.take().unwrap());
+    }
+}
+
+fn finish(recorder: Recorder) {
+    let frames = recorder.frames();
+    match recorder.finish() {
+        Ok(()) => println!("recorded {frames} frames"),
+        Err(e) => log::error!("failed to finish the recording: {e}"),
+    }
+}
+
+/// Adds the current generation to the GIF if it's one of its frames, and
+/// finishes the GIF after the last.
+fn animate(
+    args: &cli::Args,
+    start: u64,
+    animation: &mut Option<Animation>,
+    life: &mut dyn Simulation,
+) {
+    let Some(a) = animation else {
+        return;
+    };
+    let generation = life.generation();
+    if generation < start || (generation - start) % args.gif_every != 0 {
+        return;
+    }
+    if let Err(e) = args.image(life).and_then(|image| a.push(&image)) {
+        log::error!("failed to add generation {generation} to the GIF: {e}");
+    }
+    if a.frames() >= args.gif_frames {
+        finish_gif(args, animation.take().unwrap());
+    }
+}
+
+fn finish_gif(args: &cli::Args, animation: Animation) {
+    let frames = animation.frames();
+    // The GIF trailer is written when the encoder is dropped.
+    drop(animation);
+    if let Some(path) = &args.gif {
+        println!("saved {} with {frames} frames", path.display());
+    }
+}
+
+fn saves(args: &cli::Args, generation: u64) -> bool {
+    args.save_every.is_some_and(|n| generation % n == 0)
+}
+
+fn report(life: &mut dyn Simulation) {
+    let population = life.read_grid().population(life.params().lifetime());
+    println!("generation {}: {population} alive", life.generation());
+}
+
+fn save_state(life: &mut dyn Simulation, rule: &Rule, path: &Path) {
+    let snapshot = Snapshot {
+        states: rule.states,
+        ..Snapshot::capture(life)
+    };
+    match snapshot.save(path) {
+        Ok(()) => println!("generation {}: saved {}", life.generation(), path.display()),
+        Err(e) => log::error!("failed to save {}: {e}", path.display()),
+    }
+}
@@ -0,0 +1,241 @@
+//! Undo, redo and rewinding for interactive use.
+
+use std::collections::VecDeque;
+
+use crate::simulation::Simulation;
+use crate::snapshot::Snapshot;
+
+/// Keeps snapshots of a simulation to undo edits and to step backwards
+/// through recent generations.
+///
+/// Undo snapshots are taken before each edit. Rewinding instead restores the
+/// latest checkpoint at or before the target generation and simulates forward
+/// from there, so checkpoints are only taken every `interval` generations,
+/// plus once after every edit so replaying never skips one.
+pub struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// Oldest first, with increasing generations.
+    checkpoints: VecDeque<Snapshot>,
+    interval: u64,
+    /// Most snapshots kept in each of the undo stack and the checkpoints.
+    capacity: usize,
+    /// Whether the simulation was edited since the last checkpoint.
+    edited: bool,
+}
+
+impl History {
+    /// Checkpoints every `interval` generations, keeping at most `capacity`
+    /// of them and as many undo steps. Each snapshot costs 4 bytes per cell.
+    pub fn new(interval: u64, capacity: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            checkpoints: VecDeque::new(),
+            interval,
+            capacity,
+            edited: true,
+        }
+    }
+
+    /// Call before changing the simulation other than by stepping it, so the
+    /// change can be undone. Clears the redo stack.
+    pub fn record(&mut self, life: &mut dyn Simulation) {
+        push_bounded(&mut self.undo, Snapshot::capture(life), self.capacity);
+        self.redo.clear();
+        self.edited = true;
+    }
+
+    /// `record` for changes that move the simulation to another generation,
+    /// like resetting it or loading a snapshot. The checkpoints are dropped
+    /// since they belong to the line of generations that was left.
+    pub fn record_jump(&mut self, life: &mut dyn Simulation) {
+        self.record(life);
+        self.checkpoints.clear();
+    }
+
+    /// Call for further changes belonging to the last `record`, such as the
+    /// rest of a brush stroke.
+    pub fn touch(&mut self) {
+        self.edited = true;
+    }
+
+    /// Steps the simulation `n` generations, taking whatever checkpoints are
+    /// due on the way.
+    pub fn step(&mut self, life: &mut dyn Simulation, n: u32) {
+        if self.edited {
+            self.checkpoint(life);
+        }
+        life.step_n(n);
+        let recent = self
+            .checkpoints
+            .back()
+            .is_some_and(|last| life.generation() < last.generation + self.interval);
+        if !recent {
+            self.checkpoint(life);
+        }
+    }
+
+    /// Goes back to how the simulation was before the last recorded edit.
+    /// Returns false if there is nothing to undo.
+    pub fn undo(&mut self, life: &mut dyn Simulation) -> bool {
+        let Some(snapshot) = self.undo.pop_back() else {
+            return false;
+        };
+        self.redo.push(Snapshot::capture(life));
+        self.restore(life, &snapshot);
+        true
+    }
+
+    /// Reapplies the last undone edit. Returns false if there is nothing to
+    /// redo.
+    pub fn redo(&mut self, life: &mut dyn Simulation) -> bool {
+        let Some(snapshot) = self.redo.pop() else {
+            return false;
+        };
+        push_bounded(&mut self.undo, Snapshot::capture(life), self.capacity);
+        self.restore(life, &snapshot);
+        true
+    }
+
+    /// Steps back `n` generations, or as far as the checkpoints go. Returns
+    /// the number of generations actually rewound.
+    pub fn rewind(&mut self, life: &mut dyn Simulation, n: u64) -> u64 {
+        let generation = life.generation();
+        let Some(oldest) = self.checkpoints.front() else {
+            return 0;
+        };
+        let target = generation.saturating_sub(n).max(oldest.generation);
+        if target >= generation {
+            return 0;
+        }
+        // Nothing later than the target is reachable by replaying any more.
+        while self
+            .checkpoints
+            .back()
+            .is_some_and(|last| last.generation > target)
+        {
+            self.checkpoints.pop_back();
+        }
+        let checkpoint = self.checkpoints.back().unwrap();
+        checkpoint.restore(life);
+        let mut remaining = target - checkpoint.generation;
+        while remaining > 0 {
+            let n = remaining.min(u32::MAX as u64) as u32;
+            life.step_n(n);
+            remaining -= n as u64;
+        }
+        self.edited = false;
+        generation - target
+    }
+
+    /// Forgets every snapshot, for when they no longer fit the simulation
+    /// because its grid changed size.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.checkpoints.clear();
+        self.edited = true;
+    }
+
+    /// Jumps to an undo or redo snapshot. The checkpoints may belong to a
+    /// different line of edits than the snapshot, so they're all dropped.
+    fn restore(&mut self, life: &mut dyn Simulation, snapshot: &Snapshot) {
+        snapshot.restore(life);
+        self.checkpoints.clear();
+        self.edited = true;
+    }
+
+    /// Captures the current state, replacing any checkpoints from this
+    /// generation on since they no longer lead here.
+    fn checkpoint(&mut self, life: &mut dyn Simulation) {
+        let generation = life.generation();
+        while self
+            .checkpoints
+            .back()
+            .is_some_and(|last| last.generation >= generation)
+        {
+            self.checkpoints.pop_back();
+        }
+        push_bounded(
+            &mut self.checkpoints,
+            Snapshot::capture(life),
+            self.capacity,
+        );
+        self.edited = false;
+    }
+}
+
+fn push_bounded(snapshots: &mut VecDeque<Snapshot>, snapshot: Snapshot, capacity: usize) {
+    if snapshots.len() == capacity {
+        snapshots.pop_front();
+    }
+    snapshots.push_back(snapshot);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CpuLife;
+    use crate::rule::Rule;
+
+    /// An R-pentomino, which keeps changing for a long time.
+    fn life() -> CpuLife {
+        let mut data = vec![0; 32 * 32];
+        for (x, y) in [(16, 15), (17, 15), (15, 16), (16, 16), (16, 17)] {
+            data[y * 32 + x] = 1;
+        }
+        CpuLife::new(data, Rule::LIFE.params(32, 32, 1))
+    }
+
+    #[test]
+    fn rewind_replays_the_same_generations() {
+        let mut life = life();
+        let mut history = History::new(8, 16);
+        let mut generations = vec![life.read_cells()];
+        for _ in 0..40 {
+            history.step(&mut life, 1);
+            generations.push(life.read_cells());
+        }
+        assert_eq!(history.rewind(&mut life, 13), 13);
+        assert_eq!(life.generation(), 27);
+        assert_eq!(life.read_cells(), generations[27]);
+        assert_eq!(history.rewind(&mut life, 100), 27);
+        assert_eq!(life.read_cells(), generations[0]);
+        assert_eq!(history.rewind(&mut life, 1), 0);
+    }
+
+    #[test]
+    fn undo_and_redo_edits() {
+        let mut life = life();
+        let mut history = History::new(8, 16);
+        let before = life.read_cells();
+        history.record(&mut life);
+        life.set_cells(&[(0, 0)], 1);
+        let after = life.read_cells();
+
+        assert!(history.undo(&mut life));
+        assert_eq!(life.read_cells(), before);
+        assert!(!history.undo(&mut life));
+        assert!(history.redo(&mut life));
+        assert_eq!(life.read_cells(), after);
+        assert!(!history.redo(&mut life));
+    }
+
+    #[test]
+    fn jumps_drop_the_checkpoints() {
+        let mut life = life();
+        let mut history = History::new(8, 16);
+        history.step(&mut life, 20);
+        // Back to the start, but counting from a later generation, so the
+        // old checkpoints would otherwise look like earlier ones.
+        history.record_jump(&mut life);
+        life.reset();
+        life.set_generation(100);
+        assert_eq!(history.rewind(&mut life, 90), 0);
+
+        history.step(&mut life, 5);
+        assert_eq!(history.rewind(&mut life, 10), 5);
+        assert_eq!(life.generation(), 100);
+    }
+}
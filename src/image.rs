@@ -0,0 +1,360 @@
+//! Pictures of the grid, for exporting generations as image files and for
+//! reading seed images.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use crate::grid::Grid;
+
+/// Most pixels an exported image may have, a 16384 pixel square, so a large
+/// grid or scale fails cleanly rather than trying to allocate gigabytes.
+pub const MAX_PIXELS: u64 = 1 << 28;
+
+/// How cell values are turned into colours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colouring {
+    /// `value / lifetime` as grey, as the compute shader writes it to the
+    /// output texture. Live cells are white and empty ones black.
+    Raw,
+    /// The hue colouring `fs_main` in `shaders/shader.wgsl` draws the window
+    /// with.
+    Hsv,
+}
+
+impl Colouring {
+    /// Colour of a cell as 8 bit RGB.
+    pub fn rgb(self, value: u32, lifetime: u32) -> [u8; 3] {
+        let life = value as f32 / lifetime as f32;
+        let rgb = match self {
+            Colouring::Raw => [life; 3],
+            Colouring::Hsv => hsv_to_rgb(life - 0.01, 1.0, 1.0),
+        };
+        rgb.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+/// Same conversion as `hsv_to_rgb` in the render shader, including its
+/// handling of the slightly negative hue of empty cells.
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> [f32; 3] {
+    let h = h * 6.0;
+    // i32() in WGSL truncates towards zero, as does `as`.
+    let w = h as i32;
+    let f = h - w as f32;
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - s * f);
+    let t = v * (1.0 - s * (1.0 - f));
+    match w {
+        0 => [v, t, p],
+        1 => [q, v, p],
+        2 => [p, v, t],
+        3 => [p, q, v],
+        4 => [t, p, v],
+        5 => [v, p, q],
+        _ => [0.0; 3],
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    Png(String),
+    Ppm(String),
+    Gif(String),
+    UnknownFormat(String),
+    TooLarge { width: u64, height: u64 },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "{e}"),
+            ImageError::Png(e) => write!(f, "PNG error: {e}"),
+            ImageError::Ppm(e) => write!(f, "PPM error: {e}"),
+            ImageError::Gif(e) => write!(f, "GIF error: {e}"),
+            ImageError::UnknownFormat(ext) => {
+                write!(f, "unknown image format '{ext}', expected .png or .ppm")
+            }
+            ImageError::TooLarge { width, height } => write!(
+                f,
+                "a {width}x{height} image is larger than the limit of {MAX_PIXELS} pixels"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl From<io::Error> for ImageError {
+    fn from(e: io::Error) -> Self {
+        ImageError::Io(e)
+    }
+}
+
+impl From<png::EncodingError> for ImageError {
+    fn from(e: png::EncodingError) -> Self {
+        match e {
+            png::EncodingError::IoError(e) => ImageError::Io(e),
+            e => ImageError::Png(e.to_string()),
+        }
+    }
+}
+
+impl From<png::DecodingError> for ImageError {
+    fn from(e: png::DecodingError) -> Self {
+        match e {
+            png::DecodingError::IoError(e) => ImageError::Io(e),
+            e => ImageError::Png(e.to_string()),
+        }
+    }
+}
+
+impl From<gif::EncodingError> for ImageError {
+    fn from(e: gif::EncodingError) -> Self {
+        match e {
+            gif::EncodingError::Io(e) => ImageError::Io(e),
+            e => ImageError::Gif(e.to_string()),
+        }
+    }
+}
+
+/// Size in pixels of `width` by `height` cells drawn as `scale` by `scale`
+/// squares, or `ImageError::TooLarge` if that's more than [`MAX_PIXELS`].
+pub fn scaled_size(width: u32, height: u32, scale: u32) -> Result<(u32, u32), ImageError> {
+    let (width, height) = (width as u64 * scale as u64, height as u64 * scale as u64);
+    match (
+        width.checked_mul(height),
+        u32::try_from(width),
+        u32::try_from(height),
+    ) {
+        (Some(pixels), Ok(w), Ok(h)) if pixels <= MAX_PIXELS => Ok((w, h)),
+        _ => Err(ImageError::TooLarge { width, height }),
+    }
+}
+
+/// An 8 bit RGB picture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Draws every cell of `grid` as a `scale` by `scale` square, failing if
+    /// that would be more than [`MAX_PIXELS`].
+    pub fn from_grid(
+        grid: &Grid,
+        lifetime: u32,
+        colouring: Colouring,
+        scale: u32,
+    ) -> Result<Self, ImageError> {
+        let (width, height) = scaled_size(grid.width(), grid.height(), scale)?;
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let value = grid.get((x / scale) as i64, (y / scale) as i64);
+                pixels.extend_from_slice(&colouring.rgb(value, lifetime));
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), width as usize * height as usize * 3);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Row major RGB bytes.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> [u8; 3] {
+        let i = ((y * self.width + x) * 3) as usize;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    /// Brightness of a pixel between 0 and 1, weighted as in Rec. 601.
+    pub fn luminance(&self, x: u32, y: u32) -> f32 {
+        let [r, g, b] = self.get(x, y).map(|c| c as f32 / 255.0);
+        0.299 * r + 0.587 * g + 0.114 * b
+    }
+
+    /// Reads a PNG or PPM file, picking the format from the extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ImageError> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Self::load_png(path),
+            "ppm" => Self::parse_ppm(&fs::read(path)?),
+            _ => Err(ImageError::UnknownFormat(ext)),
+        }
+    }
+
+    fn load_png(path: &Path) -> Result<Self, ImageError> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        // Palettes, low bit depths and 16 bit channels all become 8 bit.
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+
+        // Transparent pixels are drawn over black.
+        let over_black = |c: u8, a: u8| (c as u16 * a as u16 / 255) as u8;
+        let pixels = match info.color_type {
+            png::ColorType::Rgb => buffer,
+            png::ColorType::Rgba => buffer
+                .chunks_exact(4)
+                .flat_map(|p| [p[0], p[1], p[2]].map(|c| over_black(c, p[3])))
+                .collect(),
+            png::ColorType::Grayscale => buffer.iter().flat_map(|&c| [c; 3]).collect(),
+            png::ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|p| [over_black(p[0], p[1]); 3])
+                .collect(),
+            png::ColorType::Indexed => {
+                return Err(ImageError::Png("palette wasn't expanded".to_string()))
+            }
+        };
+        Ok(Self::from_pixels(info.width, info.height, pixels))
+    }
+
+    /// Parses a binary (`P6`) or plain (`P3`) PPM.
+    pub fn parse_ppm(bytes: &[u8]) -> Result<Self, ImageError> {
+        let error = |message: &str| ImageError::Ppm(message.to_string());
+
+        // The header is whitespace separated fields with `#` comments,
+        // followed by a single whitespace byte before binary data.
+        let mut fields = Vec::new();
+        let mut i = 0;
+        while fields.len() < 4 {
+            match bytes.get(i) {
+                None => return Err(error("header ends early")),
+                Some(b'#') => {
+                    while bytes.get(i).is_some_and(|&b| b != b'\n') {
+                        i += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => i += 1,
+                Some(_) => {
+                    let start = i;
+                    while bytes.get(i).is_some_and(|b| !b.is_ascii_whitespace()) {
+                        i += 1;
+                    }
+                    fields.push(String::from_utf8_lossy(&bytes[start..i]).into_owned());
+                }
+            }
+        }
+        let binary = match fields[0].as_str() {
+            "P6" => true,
+            "P3" => false,
+            magic => return Err(ImageError::Ppm(format!("unsupported type '{magic}'"))),
+        };
+        let number = |field: &str| {
+            field
+                .parse::<u32>()
+                .map_err(|_| ImageError::Ppm(format!("'{field}' is not a valid number")))
+        };
+        let (width, height, max) = (
+            number(&fields[1])?,
+            number(&fields[2])?,
+            number(&fields[3])?,
+        );
+        if max == 0 || max > 65535 {
+            return Err(error("maximum value has to be between 1 and 65535"));
+        }
+        let samples = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .ok_or_else(|| ImageError::Ppm(format!("a {width}x{height} image is too large")))?;
+
+        let values = if binary {
+            let data = bytes.get(i + 1..).unwrap_or_default();
+            if max < 256 {
+                data.iter().map(|&b| b as u32).collect::<Vec<_>>()
+            } else {
+                data.chunks_exact(2)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32)
+                    .collect()
+            }
+        } else {
+            String::from_utf8_lossy(&bytes[i..])
+                .lines()
+                .map(|line| line.split('#').next().unwrap_or_default())
+                .flat_map(str::split_whitespace)
+                .map(number)
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        if values.len() < samples {
+            return Err(error("pixel data ends early"));
+        }
+        let pixels = values[..samples]
+            .iter()
+            .map(|&v| (v.min(max) * 255 / max) as u8)
+            .collect();
+        Ok(Self::from_pixels(width, height, pixels))
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), ImageError> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_scaled_cells() {
+        let grid = Grid::from_cells(2, 1, vec![2, 0]);
+        let image = Image::from_grid(&grid, 2, Colouring::Raw, 2).unwrap();
+        assert_eq!((image.width(), image.height()), (4, 2));
+        assert_eq!(image.get(1, 1), [255; 3]);
+        assert_eq!(image.get(2, 0), [0; 3]);
+    }
+
+    #[test]
+    fn rejects_oversized_images() {
+        assert_eq!(scaled_size(16384, 16384, 1).unwrap(), (16384, 16384));
+        assert!(matches!(
+            scaled_size(16384, 16384, 2),
+            Err(ImageError::TooLarge {
+                width: 32768,
+                height: 32768
+            })
+        ));
+        // Would overflow a u32 side, then a u64 pixel count.
+        assert!(scaled_size(u32::MAX, 1, 2).is_err());
+        assert!(scaled_size(u32::MAX, u32::MAX, u32::MAX).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+//! Cellular automata on the GPU, with a CPU reference engine, pattern and
+//! snapshot files and image export.
+
+pub mod animation;
+pub mod camera;
+pub mod cpu;
+pub mod gpu;
+pub mod grid;
+pub mod history;
+pub mod image;
+pub mod library;
+pub mod life;
+pub mod pattern;
+pub mod presets;
+pub mod record;
+pub mod render;
+pub mod rule;
+pub mod seed;
+pub mod simulation;
+pub mod snapshot;
+
+pub use animation::Animation;
+pub use camera::Camera;
+pub use cpu::CpuLife;
+pub use grid::{Grid, Resize};
+pub use history::History;
+pub use image::{Colouring, Image, ImageError};
+pub use library::Library;
+pub use life::{Life, Params};
+pub use pattern::{Format, Pattern, PatternError};
+pub use presets::{Preset, Presets};
+pub use record::Recorder;
+pub use render::Renderer;
+pub use rule::{Rule, RuleError};
+pub use simulation::Simulation;
+pub use snapshot::{Snapshot, SnapshotError};
@@ -0,0 +1,92 @@
+use std::path::Path;
+
+use crate::pattern::{Format, Pattern, PatternError};
+
+/// A named pattern that can be stamped into the grid.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub pattern: Pattern,
+}
+
+impl Entry {
+    fn new(name: &str, rle: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            pattern: Pattern::parse(rle, Format::Rle).unwrap(),
+        }
+    }
+}
+
+/// Registry of patterns to stamp, the built in ones followed by any loaded
+/// from pattern files.
+#[derive(Clone, Debug)]
+pub struct Library {
+    entries: Vec<Entry>,
+}
+
+impl Library {
+    pub fn builtin() -> Self {
+        let entries = vec![
+            Entry::new("Glider", "bo$2bo$3o!"),
+            Entry::new("Lightweight spaceship", "bo2bo$o4b$o3bo$4o!"),
+            Entry::new("Middleweight spaceship", "3bo2b$bo3bo$o5b$o4bo$5o!"),
+            Entry::new("Heavyweight spaceship", "3b2o2b$bo4bo$o6b$o5bo$6o!"),
+            Entry::new("R-pentomino", "b2o$2o$bo!"),
+            Entry::new("Acorn", "bo5b$3bo3b$2o2b3o!"),
+            Entry::new("Diehard", "6bob$2o6b$bo3b3o!"),
+            Entry::new("Block", "2o$2o!"),
+            Entry::new("Beehive", "b2o$o2bo$b2o!"),
+            Entry::new("Blinker", "3o!"),
+            Entry::new("Toad", "b3o$3o!"),
+            Entry::new(
+                "Pulsar",
+                "2b3o3b3o2b2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2b2$2b3o3b3o2b$\
+                 o4bobo4bo$o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+            ),
+            Entry::new("Pentadecathlon", "2bo4bo2b$2ob4ob2o$2bo4bo!"),
+            Entry::new(
+                "Gosper glider gun",
+                "24bo11b$22bobo11b$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o14b$\
+                 2o8bo3bob2o4bobo11b$10bo5bo7bo11b$11bo3bo20b$12b2o!",
+            ),
+        ];
+        Self { entries }
+    }
+
+    /// Adds a pattern file to the library, named after its first comment
+    /// line (usually `#N`) or else the file name.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<(), PatternError> {
+        let path = path.as_ref();
+        let pattern = Pattern::load(path)?;
+        let name = match pattern.comments.first() {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ => path.file_stem().map_or_else(
+                || path.display().to_string(),
+                |s| s.to_string_lossy().into(),
+            ),
+        };
+        self.entries.push(Entry { name, pattern });
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+}
+
+impl std::ops::Index<usize> for Library {
+    type Output = Entry;
+
+    fn index(&self, index: usize) -> &Entry {
+        &self.entries[index]
+    }
+}
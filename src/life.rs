@@ -1,6 +1,13 @@
 use bytemuck::{Pod, Zeroable};
 use std::borrow::Cow;
-use wgpu::{util::DeviceExt, BindGroup, Buffer, ComputePipeline, Device, Queue};
+use std::sync::Arc;
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, Buffer, ComputePipeline, Device, Queue, Texture,
+};
+
+use crate::gpu;
+use crate::grid::{Grid, Resize};
+use crate::simulation::{self, Simulation};
 
 const WORKGROUP_SIZE: (u32, u32) = (16, 16);
 
@@ -62,33 +69,58 @@ impl Params {
             d_rule_8: dead_rules[8],
         }
     }
+
+    pub fn lifetime(&self) -> u32 {
+        self.lifetime
+    }
+
+    pub fn alive_rules(&self) -> [u32; 9] {
+        [
+            self.a_rule_0,
+            self.a_rule_1,
+            self.a_rule_2,
+            self.a_rule_3,
+            self.a_rule_4,
+            self.a_rule_5,
+            self.a_rule_6,
+            self.a_rule_7,
+            self.a_rule_8,
+        ]
+    }
+
+    pub fn dead_rules(&self) -> [u32; 9] {
+        [
+            self.d_rule_0,
+            self.d_rule_1,
+            self.d_rule_2,
+            self.d_rule_3,
+            self.d_rule_4,
+            self.d_rule_5,
+            self.d_rule_6,
+            self.d_rule_7,
+            self.d_rule_8,
+        ]
+    }
 }
 
-pub struct Life {
-    params: Params,
-    data: Vec<u32>,
-    compute_pipeline: ComputePipeline,
+/// Everything sized by the grid, which is rebuilt when it's resized while the
+/// pipelines are kept.
+struct GridResources {
     compute_bind_group: BindGroup,
     compute_input_buffer: Buffer,
     compute_output_buffer: Buffer,
+    compute_param_buffer: Buffer,
+    staging_buffer: Buffer,
+    output_texture: Texture,
 }
 
-impl Life {
-    pub fn new(
-        data: Vec<u32>,
-        params: Params,
-        device: &Device,
-        output_texture_view: &wgpu::TextureView,
-    ) -> Self {
-        let cs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/life.wgsl"))),
-        });
-        let slice_size = data.len() * std::mem::size_of::<u32>();
+impl GridResources {
+    fn new(device: &Device, layout: &BindGroupLayout, data: &[u32], params: &Params) -> Self {
+        let slice_size = std::mem::size_of_val(data);
         let size = slice_size as wgpu::BufferAddress;
         let compute_input_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Input Buffer"),
-            contents: bytemuck::cast_slice(&data),
+            contents: bytemuck::cast_slice(data),
             usage: wgpu::BufferUsages::STORAGE
                 | wgpu::BufferUsages::COPY_DST
                 | wgpu::BufferUsages::COPY_SRC,
@@ -99,20 +131,24 @@ impl Life {
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
             mapped_at_creation: false,
         });
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Staging Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
         let compute_param_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("parameters buffer"),
-            contents: bytemuck::bytes_of(&params),
+            contents: bytemuck::bytes_of(params),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
-        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("`compute pipeline`"),
-            layout: None,
-            module: &cs_module,
-            entry_point: "main",
-        });
+        let output_texture = gpu::create_output_texture(device, params);
+        let output_texture_view =
+            output_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
         let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Compute shader bind group"),
-            layout: &compute_pipeline.get_bind_group_layout(0),
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -134,45 +170,421 @@ impl Life {
         });
 
         Self {
+            compute_bind_group,
+            compute_input_buffer,
+            compute_output_buffer,
+            compute_param_buffer,
+            staging_buffer,
+            output_texture,
+        }
+    }
+}
+
+pub struct Life {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    params: Params,
+    /// Cells the simulation started from, resized with the grid, for `reset`.
+    data: Vec<u32>,
+    /// Lifetime `data` was written for, it's rescaled to the current one when
+    /// resetting.
+    data_lifetime: u32,
+    generation: u64,
+    compute_pipeline: ComputePipeline,
+    draw_pipeline: ComputePipeline,
+    compute_bind_group_layout: BindGroupLayout,
+    compute_bind_group: BindGroup,
+    compute_input_buffer: Buffer,
+    compute_output_buffer: Buffer,
+    compute_param_buffer: Buffer,
+    staging_buffer: Buffer,
+    output_texture: Texture,
+}
+
+impl Life {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, data: Vec<u32>, params: Params) -> Self {
+        assert_eq!(data.len(), (params.width * params.height) as usize);
+        let cs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/life.wgsl"))),
+        });
+        // Both entry points share one layout, `draw` just doesn't touch the
+        // output buffer.
+        let compute_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Compute shader bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: wgpu::TextureFormat::Rgba32Float,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Compute Pipeline Layout"),
+                bind_group_layouts: &[&compute_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("`compute pipeline`"),
+            layout: Some(&compute_pipeline_layout),
+            module: &cs_module,
+            entry_point: "main",
+        });
+        let draw_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("`draw pipeline`"),
+            layout: Some(&compute_pipeline_layout),
+            module: &cs_module,
+            entry_point: "draw",
+        });
+        let GridResources {
+            compute_bind_group,
+            compute_input_buffer,
+            compute_output_buffer,
+            compute_param_buffer,
+            staging_buffer,
+            output_texture,
+        } = GridResources::new(&device, &compute_bind_group_layout, &data, &params);
+
+        let life = Self {
+            device,
+            queue,
             params,
+            data_lifetime: params.lifetime(),
             data,
+            generation: 0,
             compute_pipeline,
+            draw_pipeline,
+            compute_bind_group_layout,
             compute_bind_group,
             compute_input_buffer,
             compute_output_buffer,
-        }
+            compute_param_buffer,
+            staging_buffer,
+            output_texture,
+        };
+        life.draw();
+        life
+    }
+
+    fn dispatch<'a>(&'a self, cpass: &mut wgpu::ComputePass<'a>, pipeline: &'a ComputePipeline) {
+        cpass.set_pipeline(pipeline);
+        cpass.set_bind_group(0, &self.compute_bind_group, &[]);
+
+        // Round up so grids that aren't a multiple of the workgroup size are
+        // fully covered, the shader discards the out of bounds invocations.
+        let xgroups = self.params.width.div_ceil(WORKGROUP_SIZE.0);
+        let ygroups = self.params.height.div_ceil(WORKGROUP_SIZE.1);
+
+        cpass.dispatch_workgroups(xgroups, ygroups, 1);
+        // Number of cells to run, the (x,y,z) size of item being processed
     }
 
-    pub async fn step(&mut self, device: &Device, queue: &Queue) {
-        let mut encoder =
-            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    /// Colours the output texture from the current generation without
+    /// advancing it, used after the cells are written from the CPU.
+    fn draw(&self) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
         {
             let mut cpass =
                 encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
-            cpass.set_pipeline(&self.compute_pipeline);
-            cpass.set_bind_group(0, &self.compute_bind_group, &[]);
-            cpass.insert_debug_marker("compute shader");
+            cpass.insert_debug_marker("draw shader");
+            self.dispatch(&mut cpass, &self.draw_pipeline);
+        }
+        self.queue.submit(Some(encoder.finish()));
+    }
 
-            let xgroups = self.params.width as u32 / WORKGROUP_SIZE.0;
-            let ygroups = self.params.height as u32 / WORKGROUP_SIZE.0;
+    fn buffer_size(&self) -> wgpu::BufferAddress {
+        let slice_size = self.data.len() * std::mem::size_of::<u32>();
+        slice_size as wgpu::BufferAddress
+    }
+
+    /// Copies the current generation back from the GPU.
+    pub async fn read_state(&self) -> Vec<u32> {
+        let size = self.buffer_size();
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(&self.compute_input_buffer, 0, &self.staging_buffer, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+        self.read_staging(size).await
+    }
 
-            cpass.dispatch_workgroups(xgroups, ygroups, 1);
-            // Number of cells to run, the (x,y,z) size of item being processed
+    /// Reads back just the grid rows `rows`, one after another.
+    async fn read_rows(&self, rows: impl Iterator<Item = u32>) -> Vec<u32> {
+        let row_size = self.params.width as wgpu::BufferAddress * 4;
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let mut size = 0;
+        for row in rows {
+            encoder.copy_buffer_to_buffer(
+                &self.compute_input_buffer,
+                row as wgpu::BufferAddress * row_size,
+                &self.staging_buffer,
+                size,
+                row_size,
+            );
+            size += row_size;
         }
-        let slice_size = self.data.len() * std::mem::size_of::<u32>();
-        let size = slice_size as wgpu::BufferAddress;
+        self.queue.submit(Some(encoder.finish()));
+        self.read_staging(size).await
+    }
 
-        // Sets adds copy operation to command encoder.
-        // Will copy data from storage buffer on GPU to staging buffer on CPU.
-        encoder.copy_buffer_to_buffer(
-            &self.compute_output_buffer,
-            0,
-            &self.compute_input_buffer,
-            0,
-            size,
+    /// The first `size` bytes of the staging buffer, once the copies into it
+    /// have finished.
+    async fn read_staging(&self, size: wgpu::BufferAddress) -> Vec<u32> {
+        let buffer_slice = self.staging_buffer.slice(..size);
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+
+        // Blocks until the copies have finished and the map callback ran.
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .receive()
+            .await
+            .expect("staging buffer map callback dropped")
+            .expect("failed to map staging buffer");
+
+        let data = buffer_slice.get_mapped_range();
+        let cells = bytemuck::cast_slice(&data).to_vec();
+        drop(data);
+        self.staging_buffer.unmap();
+        cells
+    }
+}
+
+impl Simulation for Life {
+    fn params(&self) -> Params {
+        self.params
+    }
+
+    fn set_params(&mut self, params: Params) {
+        assert_eq!(
+            (params.width, params.height),
+            (self.params.width, self.params.height),
+            "set_params can't change the grid size, use resize"
         );
+        self.params = params;
+        self.queue
+            .write_buffer(&self.compute_param_buffer, 0, bytemuck::bytes_of(&params));
+        // The colours depend on the lifetime.
+        self.draw();
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    /// Records all `n` generations into a single command encoder.
+    fn step_n(&mut self, n: u32) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for _ in 0..n {
+            {
+                let mut cpass =
+                    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+                cpass.insert_debug_marker("compute shader");
+                self.dispatch(&mut cpass, &self.compute_pipeline);
+            }
+
+            // The output becomes the input of the next generation.
+            encoder.copy_buffer_to_buffer(
+                &self.compute_output_buffer,
+                0,
+                &self.compute_input_buffer,
+                0,
+                self.buffer_size(),
+            );
+        }
 
         // Submits command encoder for processing
-        queue.submit(Some(encoder.finish()));
+        self.queue.submit(Some(encoder.finish()));
+        self.generation += n as u64;
+    }
+
+    fn read_cells(&mut self) -> Vec<u32> {
+        pollster::block_on(self.read_state())
+    }
+
+    fn write_cells(&mut self, cells: &[u32]) {
+        assert_eq!(cells.len(), self.data.len());
+        self.queue
+            .write_buffer(&self.compute_input_buffer, 0, bytemuck::cast_slice(cells));
+        self.draw();
+    }
+
+    /// Only copies back the rows the region covers.
+    fn read_region(&mut self, x: i64, y: i64, width: u32, height: u32) -> Grid {
+        let (grid_width, grid_height) = (self.params.width as i64, self.params.height as i64);
+        let rows = (0..height as i64).map(|dy| (y + dy).rem_euclid(grid_height) as u32);
+        let rows = Grid::from_cells(
+            self.params.width,
+            height,
+            pollster::block_on(self.read_rows(rows)),
+        );
+        rows.region(x.rem_euclid(grid_width), 0, width, height)
+    }
+
+    /// Writes each row of the region straight into the cell buffer, in two
+    /// parts where it wraps around the right edge.
+    fn write_region(&mut self, x: i64, y: i64, cells: &Grid) {
+        let (width, height) = (self.params.width, self.params.height);
+        assert!(cells.width() <= width && cells.height() <= height);
+        let x = x.rem_euclid(width as i64) as u32;
+        // Columns before the right edge, the rest wrap around to column 0.
+        let split = cells.width().min(width - x) as usize;
+        for (dy, row) in cells
+            .cells()
+            .chunks_exact(cells.width() as usize)
+            .enumerate()
+        {
+            let row_start = (y + dy as i64).rem_euclid(height as i64) as u32 * width;
+            let (right, left) = row.split_at(split);
+            for (start, part) in [(row_start + x, right), (row_start, left)] {
+                if !part.is_empty() {
+                    self.queue.write_buffer(
+                        &self.compute_input_buffer,
+                        start as wgpu::BufferAddress * 4,
+                        bytemuck::cast_slice(part),
+                    );
+                }
+            }
+        }
+        self.draw();
+    }
+
+    fn set_cells(&mut self, positions: &[(u32, u32)], value: u32) {
+        for &(x, y) in positions {
+            assert!(x < self.params.width && y < self.params.height);
+            let offset = (y * self.params.width + x) as wgpu::BufferAddress * 4;
+            self.queue.write_buffer(
+                &self.compute_input_buffer,
+                offset,
+                bytemuck::bytes_of(&value),
+            );
+        }
+        self.draw();
+    }
+
+    fn reset(&mut self) {
+        let (from, to) = (self.data_lifetime, self.params.lifetime());
+        let cells = self
+            .data
+            .iter()
+            .map(|&cell| simulation::rescale(cell, from, to))
+            .collect::<Vec<_>>();
+        self.queue
+            .write_buffer(&self.compute_input_buffer, 0, bytemuck::cast_slice(&cells));
+        self.generation = 0;
+        self.draw();
+    }
+
+    /// Rebuilds the buffers, output texture and bind group at the new size,
+    /// keeping the pipelines.
+    fn resize(&mut self, width: u32, height: u32, cells: Vec<u32>, resize: Resize) {
+        assert_eq!(cells.len(), (width * height) as usize);
+        let data = std::mem::take(&mut self.data);
+        self.data = Grid::from_cells(self.params.width, self.params.height, data)
+            .resized(width, height, resize)
+            .into_cells();
+        self.params.width = width;
+        self.params.height = height;
+        let resources = GridResources::new(
+            &self.device,
+            &self.compute_bind_group_layout,
+            &cells,
+            &self.params,
+        );
+        self.compute_bind_group = resources.compute_bind_group;
+        self.compute_input_buffer = resources.compute_input_buffer;
+        self.compute_output_buffer = resources.compute_output_buffer;
+        self.compute_param_buffer = resources.compute_param_buffer;
+        self.staging_buffer = resources.staging_buffer;
+        self.output_texture = resources.output_texture;
+        self.draw();
+    }
+
+    fn output_texture(&self) -> Option<&Texture> {
+        Some(&self.output_texture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CpuLife;
+    use crate::rule::Rule;
+    use crate::seed;
+
+    #[test]
+    #[ignore = "needs a GPU adapter, software ones such as lavapipe will do"]
+    fn matches_the_cpu_engine() {
+        let instance = wgpu::Instance::default();
+        let (_, device, queue) = pollster::block_on(gpu::request_device(&instance, None, false));
+        let (device, queue) = (Arc::new(device), Arc::new(queue));
+        // Two state, decaying and Generations rules, on a grid that isn't a
+        // whole number of workgroups.
+        for (rule, lifetime) in [("B3/S23", 1), ("B36/S23", 20), ("B2/S/C3", 1)] {
+            let rule = rule.parse::<Rule>().unwrap();
+            let params = rule.params(67, 45, lifetime);
+            let data = seed::soup(67, 45, params.lifetime(), &seed::Soup::new(7));
+            let mut gpu = Life::new(device.clone(), queue.clone(), data.clone(), params);
+            let mut cpu = CpuLife::new(data, params);
+            for _ in 0..10 {
+                gpu.step_n(13);
+                cpu.step_n(13);
+                assert_eq!(
+                    pollster::block_on(gpu.read_state()),
+                    cpu.read_cells(),
+                    "{rule} after {} generations",
+                    cpu.generation()
+                );
+            }
+        }
     }
 }
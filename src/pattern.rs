@@ -0,0 +1,338 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::grid::Grid;
+
+pub mod cells;
+pub mod life;
+pub mod rle;
+
+/// Most cells a pattern may have, well beyond any grid that fits on the GPU.
+/// Stops a bogus size in a pattern file from exhausting memory.
+pub const MAX_CELLS: usize = 1 << 26;
+
+/// A rectangle of cells, as read from or written to a pattern file. Cells are
+/// numbered as in multi-state RLE: 0 is dead, 1 alive and each state after
+/// that a generation further decayed. Placing a pattern sets its live cells to
+/// the lifetime and counts the others down from there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    width: u32,
+    height: u32,
+    cells: Vec<u32>,
+    /// Rule given by the file, if any, exactly as written.
+    pub rule: Option<String>,
+    /// Name and comment lines from the file.
+    pub comments: Vec<String>,
+}
+
+/// Pattern file formats that can be read and written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Run Length Encoded, `.rle`.
+    Rle,
+    /// Plaintext, `.cells`.
+    Cells,
+    /// Life 1.05, `.lif` or `.life`.
+    Life105,
+    /// Life 1.06, `.lif` or `.life`.
+    Life106,
+}
+
+impl Format {
+    /// Picks the format from the file extension. Life files are read as
+    /// whichever version their header says, but written as 1.06.
+    pub fn from_path(path: &Path) -> Result<Self, PatternError> {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "rle" => Ok(Format::Rle),
+            "cells" => Ok(Format::Cells),
+            "lif" | "life" => Ok(Format::Life106),
+            _ => Err(PatternError::UnknownFormat(ext)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PatternError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    UnknownFormat(String),
+    TooLarge { width: u64, height: u64 },
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Io(e) => write!(f, "{e}"),
+            PatternError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            PatternError::UnknownFormat(ext) => write!(
+                f,
+                "unknown pattern format '{ext}', expected .rle, .cells or .lif"
+            ),
+            PatternError::TooLarge { width, height } => write!(
+                f,
+                "a {width}x{height} pattern is larger than the limit of {MAX_CELLS} cells"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl From<io::Error> for PatternError {
+    fn from(e: io::Error) -> Self {
+        PatternError::Io(e)
+    }
+}
+
+impl PatternError {
+    fn parse(line: usize, message: impl Into<String>) -> Self {
+        PatternError::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl Pattern {
+    /// An empty pattern. Panics if it would have more than [`MAX_CELLS`]
+    /// cells, sizes read from a file should go through `try_new`.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::try_new(width, height).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// An empty pattern, or `PatternError::TooLarge` if it would have more
+    /// than [`MAX_CELLS`] cells.
+    pub fn try_new(width: u32, height: u32) -> Result<Self, PatternError> {
+        Self::sized(width as u64, height as u64)
+    }
+
+    /// `try_new` for sizes that may not even fit a `u32`.
+    fn sized(width: u64, height: u64) -> Result<Self, PatternError> {
+        let cells = usize::try_from(width)
+            .ok()
+            .zip(usize::try_from(height).ok())
+            .and_then(|(width, height)| width.checked_mul(height))
+            .filter(|&cells| cells <= MAX_CELLS);
+        match (cells, u32::try_from(width), u32::try_from(height)) {
+            (Some(cells), Ok(width), Ok(height)) => Ok(Self {
+                width,
+                height,
+                cells: vec![0; cells],
+                rule: None,
+                comments: Vec::new(),
+            }),
+            _ => Err(PatternError::TooLarge { width, height }),
+        }
+    }
+
+    /// Builds a pattern from a list of live cell coordinates, sized to fit
+    /// them. Coordinates may be negative, the pattern starts at the smallest.
+    /// Fails with `PatternError::TooLarge` if the cells are too far apart.
+    pub fn from_coordinates(coordinates: &[(i64, i64)]) -> Result<Self, PatternError> {
+        let cells = coordinates
+            .iter()
+            .map(|&(x, y)| (x, y, 1))
+            .collect::<Vec<_>>();
+        Self::from_states(&cells)
+    }
+
+    /// `from_coordinates` for cells in any state, given as `(x, y, state)`.
+    fn from_states(cells: &[(i64, i64, u32)]) -> Result<Self, PatternError> {
+        let Some(&(x0, y0, _)) = cells.first() else {
+            return Ok(Self::new(0, 0));
+        };
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (x0, y0, x0, y0);
+        for &(x, y, _) in cells {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        // Far apart cells can span more than even an i64 holds.
+        let span = |min: i64, max: i64| max.abs_diff(min).saturating_add(1);
+        let mut pattern = Self::sized(span(min_x, max_x), span(min_y, max_y))?;
+        for &(x, y, state) in cells {
+            pattern.set_state(x.abs_diff(min_x) as u32, y.abs_diff(min_y) as u32, state);
+        }
+        Ok(pattern)
+    }
+
+    /// Copies the `width` by `height` region of `grid` at `x`, `y`, wrapping
+    /// around its edges. Cells equal to `lifetime` are alive.
+    pub fn from_grid(grid: &Grid, x: i64, y: i64, width: u32, height: u32, lifetime: u32) -> Self {
+        let mut pattern = Self::new(width, height);
+        for py in 0..height {
+            for px in 0..width {
+                let alive = grid.get(x + px as i64, y + py as i64) == lifetime;
+                pattern.set(px, py, alive);
+            }
+        }
+        pattern
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Whether the cell at `x`, `y` is alive.
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        self.state(x, y) == 1
+    }
+
+    /// Makes the cell at `x`, `y` alive or dead.
+    pub fn set(&mut self, x: u32, y: u32, alive: bool) {
+        self.set_state(x, y, alive as u32);
+    }
+
+    /// State of the cell at `x`, `y`, see [`Pattern`].
+    pub fn state(&self, x: u32, y: u32) -> u32 {
+        self.cells[(y * self.width + x) as usize]
+    }
+
+    fn set_state(&mut self, x: u32, y: u32, state: u32) {
+        self.cells[(y * self.width + x) as usize] = state;
+    }
+
+    /// Value the cell at `x`, `y` is given when placed with `lifetime`:
+    /// `lifetime` when alive, one less for each state after that and zero
+    /// once it would have decayed completely.
+    pub fn value(&self, x: u32, y: u32, lifetime: u32) -> u32 {
+        match self.state(x, y) {
+            0 => 0,
+            state => lifetime.saturating_sub(state - 1),
+        }
+    }
+
+    /// Coordinates of every live cell, row by row.
+    pub fn live_cells(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, &state)| state == 1)
+            .map(|(i, _)| (i as u32 % self.width, i as u32 / self.width))
+    }
+
+    pub fn population(&self) -> usize {
+        self.cells.iter().filter(|&&state| state == 1).count()
+    }
+
+    /// The smallest pattern containing every live or decaying cell of this
+    /// one.
+    pub fn trimmed(&self) -> Self {
+        let cells = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(_, &state)| state != 0)
+            .map(|(i, &state)| {
+                let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+                (x as i64, y as i64, state)
+            })
+            .collect::<Vec<_>>();
+        let trimmed = Self::from_states(&cells).expect("trimming never grows a pattern");
+        Self {
+            rule: self.rule.clone(),
+            comments: self.comments.clone(),
+            ..trimmed
+        }
+    }
+
+    /// The pattern turned a quarter turn clockwise.
+    pub fn rotated(&self) -> Self {
+        self.transformed(self.height, self.width, |x, y| (y, self.height - 1 - x))
+    }
+
+    /// The pattern mirrored left to right.
+    pub fn flipped_horizontally(&self) -> Self {
+        self.transformed(self.width, self.height, |x, y| (self.width - 1 - x, y))
+    }
+
+    /// The pattern mirrored top to bottom.
+    pub fn flipped_vertically(&self) -> Self {
+        self.transformed(self.width, self.height, |x, y| (x, self.height - 1 - y))
+    }
+
+    /// A `width` by `height` pattern whose cell at `x`, `y` is this one's at
+    /// `source(x, y)`.
+    fn transformed(
+        &self,
+        width: u32,
+        height: u32,
+        source: impl Fn(u32, u32) -> (u32, u32),
+    ) -> Self {
+        let mut pattern = Self {
+            rule: self.rule.clone(),
+            comments: self.comments.clone(),
+            ..Self::new(width, height)
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = source(x, y);
+                pattern.set_state(x, y, self.state(sx, sy));
+            }
+        }
+        pattern
+    }
+
+    /// Writes the pattern into `grid` with its top left corner at `x`, `y`,
+    /// wrapping around the edges. Each cell is set to its `value` and dead
+    /// ones cleared.
+    pub fn place(&self, grid: &mut Grid, x: i64, y: i64, lifetime: u32) {
+        for py in 0..self.height {
+            for px in 0..self.width {
+                grid.set(x + px as i64, y + py as i64, self.value(px, py, lifetime));
+            }
+        }
+    }
+
+    /// Parses the contents of a pattern file. Both Life versions accept
+    /// either, going by the header.
+    pub fn parse(contents: &str, format: Format) -> Result<Self, PatternError> {
+        match format {
+            Format::Rle => rle::parse(contents),
+            Format::Cells => cells::parse(contents),
+            Format::Life105 | Format::Life106 => life::parse(contents),
+        }
+    }
+
+    /// Writes the pattern out in `format`.
+    pub fn write(&self, format: Format) -> String {
+        match format {
+            Format::Rle => rle::write(self),
+            Format::Cells => cells::write(self),
+            Format::Life105 => life::write_105(self),
+            Format::Life106 => life::write_106(self),
+        }
+    }
+
+    /// Reads a pattern file, picking the format from the extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PatternError> {
+        let path = path.as_ref();
+        let format = Format::from_path(path)?;
+        Self::parse(&fs::read_to_string(path)?, format)
+    }
+
+    /// Writes a pattern file, picking the format from the extension.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PatternError> {
+        let path = path.as_ref();
+        self.save_as(path, Format::from_path(path)?)
+    }
+
+    /// Writes a pattern file in `format` whatever its extension.
+    pub fn save_as(&self, path: impl AsRef<Path>, format: Format) -> Result<(), PatternError> {
+        fs::write(path, self.write(format))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+//! Plaintext patterns, as used by LifeWiki for `.cells` files.
+//!
+//! ```text
+//! !Name: Glider
+//! .O
+//! ..O
+//! OOO
+//! ```
+
+use super::{Pattern, PatternError};
+
+pub fn parse(contents: &str) -> Result<Pattern, PatternError> {
+    let mut comments = Vec::new();
+    let mut rows = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim_end();
+        if let Some(comment) = line.strip_prefix('!') {
+            let comment = comment.trim();
+            let comment = comment.strip_prefix("Name:").unwrap_or(comment).trim();
+            comments.push(comment.to_string());
+            continue;
+        }
+        let mut row = Vec::new();
+        for c in line.chars() {
+            match c {
+                '.' => row.push(false),
+                // `O` is standard, `*` turns up in older collections.
+                'O' | '*' => row.push(true),
+                c => {
+                    return Err(PatternError::parse(
+                        i + 1,
+                        format!("unexpected '{c}', expected '.' or 'O'"),
+                    ))
+                }
+            }
+        }
+        rows.push(row);
+    }
+    // Trailing blank lines aren't part of the pattern.
+    while rows.last().is_some_and(|row| row.is_empty()) {
+        rows.pop();
+    }
+
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut pattern = Pattern::sized(width as u64, rows.len() as u64)?;
+    for (y, row) in rows.iter().enumerate() {
+        for (x, &alive) in row.iter().enumerate() {
+            pattern.set(x as u32, y as u32, alive);
+        }
+    }
+    pattern.comments = comments;
+    Ok(pattern)
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = String::new();
+    let mut comments = pattern.comments.iter();
+    if let Some(name) = comments.next() {
+        out.push_str(&format!("!Name: {name}\n"));
+    }
+    for comment in comments {
+        out.push_str(&format!("!{comment}\n"));
+    }
+    for y in 0..pattern.height() {
+        let row = (0..pattern.width())
+            .map(|x| if pattern.get(x, y) { 'O' } else { '.' })
+            .collect::<String>();
+        out.push_str(row.trim_end_matches('.'));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "!Name: Glider\n!A comment\n.O\n..O\nOOO\n";
+
+    #[test]
+    fn parses_a_glider() {
+        let pattern = parse(GLIDER).unwrap();
+        assert_eq!(pattern.comments, ["Glider", "A comment"]);
+        assert_eq!((pattern.width(), pattern.height()), (3, 3));
+        assert_eq!(
+            pattern.live_cells().collect::<Vec<_>>(),
+            [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let pattern = parse(GLIDER).unwrap();
+        assert_eq!(write(&pattern), GLIDER);
+        assert_eq!(parse(&write(&pattern)).unwrap(), pattern);
+    }
+
+    #[test]
+    fn accepts_asterisks_and_ignores_trailing_blank_lines() {
+        let pattern = parse(".*\n..*\n***\n\n\n").unwrap();
+        assert_eq!(
+            pattern,
+            Pattern {
+                comments: Vec::new(),
+                ..parse(GLIDER).unwrap()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(
+            parse(".O\n..X\nOOO\n").unwrap_err().to_string(),
+            "line 2: unexpected 'X', expected '.' or 'O'"
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_patterns() {
+        let wide = ".".repeat(1 << 14);
+        let contents = format!("{wide}O\n{}O\n", "\n".repeat(1 << 13));
+        assert!(matches!(
+            parse(&contents),
+            Err(PatternError::TooLarge { .. })
+        ));
+    }
+}
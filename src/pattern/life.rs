@@ -0,0 +1,325 @@
+//! The Life 1.05 and 1.06 formats, both usually saved as `.lif`.
+//!
+//! Life 1.05 draws blocks of cells relative to the centre of the pattern:
+//!
+//! ```text
+//! #Life 1.05
+//! #D Glider
+//! #N
+//! #P -1 -1
+//! .*
+//! ..*
+//! ***
+//! ```
+//!
+//! Life 1.06 just lists the coordinates of live cells, one per line.
+
+use super::{Pattern, PatternError};
+use crate::rule::Rule;
+
+/// Widest block `write_105` produces, the line limit of the format.
+const LINE_LENGTH: u32 = 80;
+
+/// Parses either version, going by the `#Life` header on the first line.
+pub fn parse(contents: &str) -> Result<Pattern, PatternError> {
+    let header = contents.lines().next().unwrap_or_default().trim();
+    match header {
+        "#Life 1.05" => parse_105(contents),
+        "#Life 1.06" => parse_106(contents),
+        header if header.starts_with("#Life") => Err(PatternError::parse(
+            1,
+            format!("unsupported version '{header}', expected 1.05 or 1.06"),
+        )),
+        _ => Err(PatternError::parse(
+            1,
+            "missing '#Life 1.05' or '#Life 1.06' header",
+        )),
+    }
+}
+
+fn parse_105(contents: &str) -> Result<Pattern, PatternError> {
+    let mut comments = Vec::new();
+    let mut rule = None;
+    let mut live = Vec::new();
+    let mut block = (0i64, 0i64);
+    let mut y = 0i64;
+
+    for (i, line) in contents.lines().enumerate().skip(1) {
+        let number = i + 1;
+        let line = line.trim();
+        if let Some(line) = line.strip_prefix('#') {
+            let mut chars = line.chars();
+            let kind = chars.next();
+            let rest = chars.as_str().trim();
+            match kind {
+                Some('D') => comments.push(rest.to_string()),
+                Some('N') => rule = Some(Rule::LIFE.to_string()),
+                Some('R') => rule = Some(rest.to_string()),
+                Some('P') => {
+                    block = parse_coordinates(rest).map_err(|m| PatternError::parse(number, m))?;
+                    y = 0;
+                }
+                _ => {
+                    return Err(PatternError::parse(
+                        number,
+                        format!("unknown line '#{line}'"),
+                    ))
+                }
+            }
+            continue;
+        }
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                '.' => {}
+                '*' => {
+                    let cell = block.0.checked_add(x as i64).zip(block.1.checked_add(y));
+                    live.push(cell.ok_or_else(|| {
+                        PatternError::parse(number, "cell coordinates are out of range")
+                    })?);
+                }
+                c => {
+                    return Err(PatternError::parse(
+                        number,
+                        format!("unexpected '{c}', expected '.' or '*'"),
+                    ))
+                }
+            }
+        }
+        y += 1;
+    }
+
+    let mut pattern = Pattern::from_coordinates(&live)?;
+    pattern.rule = rule;
+    pattern.comments = comments;
+    Ok(pattern)
+}
+
+fn parse_106(contents: &str) -> Result<Pattern, PatternError> {
+    let mut live = Vec::new();
+    for (i, line) in contents.lines().enumerate().skip(1) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        live.push(parse_coordinates(line).map_err(|m| PatternError::parse(i + 1, m))?);
+    }
+    Pattern::from_coordinates(&live)
+}
+
+/// Parses a whitespace separated `x y` pair.
+fn parse_coordinates(s: &str) -> Result<(i64, i64), String> {
+    let mut parts = s.split_whitespace();
+    let mut next = || {
+        let part = parts
+            .next()
+            .ok_or_else(|| format!("expected 'x y' coordinates, found '{s}'"))?;
+        part.parse::<i64>()
+            .map_err(|_| format!("'{part}' is not a whole number"))
+    };
+    let coordinates = (next()?, next()?);
+    match parts.next() {
+        Some(_) => Err(format!("expected 'x y' coordinates, found '{s}'")),
+        None => Ok(coordinates),
+    }
+}
+
+/// Writes Life 1.05, centred on the origin and split into blocks no wider than
+/// the line limit.
+pub fn write_105(pattern: &Pattern) -> String {
+    let mut out = String::from("#Life 1.05\n");
+    for comment in &pattern.comments {
+        out.push_str(&format!("#D {comment}\n"));
+    }
+    // 1.05 writes rules survival first, and can't express Generations rules.
+    match pattern.rule.as_deref().map(str::parse::<Rule>) {
+        Some(Ok(rule)) if rule == Rule::LIFE => out.push_str("#N\n"),
+        Some(Ok(rule)) if rule.states.is_none() => out.push_str(&format!(
+            "#R {}/{}\n",
+            neighbours(&rule.survival),
+            neighbours(&rule.birth)
+        )),
+        _ => {}
+    }
+
+    let (left, top) = (
+        -(pattern.width() as i64 / 2),
+        -(pattern.height() as i64 / 2),
+    );
+    for x0 in (0..pattern.width()).step_by(LINE_LENGTH as usize) {
+        let x1 = (x0 + LINE_LENGTH).min(pattern.width());
+        let rows = (0..pattern.height())
+            .map(|y| {
+                let row = (x0..x1)
+                    .map(|x| if pattern.get(x, y) { '*' } else { '.' })
+                    .collect::<String>();
+                row.trim_end_matches('.').to_string()
+            })
+            .collect::<Vec<_>>();
+        let Some(first) = rows.iter().position(|row| !row.is_empty()) else {
+            continue;
+        };
+        let last = rows.iter().rposition(|row| !row.is_empty()).unwrap();
+        out.push_str(&format!("#P {} {}\n", left + x0 as i64, top + first as i64));
+        for row in &rows[first..=last] {
+            // An empty line would read as the end of the block to some
+            // programs, so blank rows keep a single dead cell.
+            out.push_str(if row.is_empty() { "." } else { row });
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Writes Life 1.06. The format has no room for the rule or comments.
+pub fn write_106(pattern: &Pattern) -> String {
+    let mut out = String::from("#Life 1.06\n");
+    for (x, y) in pattern.live_cells() {
+        out.push_str(&format!("{x} {y}\n"));
+    }
+    out
+}
+
+fn neighbours(counts: &[u32; 9]) -> String {
+    (0..9)
+        .filter(|&n| counts[n] != 0)
+        .map(|n| char::from_digit(n as u32, 10).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glider() -> Pattern {
+        let mut pattern = Pattern::new(3, 3);
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            pattern.set(x, y, true);
+        }
+        pattern
+    }
+
+    #[test]
+    fn parses_105() {
+        let pattern = parse("#Life 1.05\n#D Glider\n#N\n#P -1 -1\n.*\n..*\n***\n").unwrap();
+        assert_eq!(pattern.comments, ["Glider"]);
+        assert_eq!(pattern.rule.as_deref(), Some("B3/S23"));
+        assert_eq!(
+            pattern.live_cells().collect::<Vec<_>>(),
+            glider().live_cells().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn parses_105_blocks() {
+        // Two blocks placed relative to each other, with a gap between.
+        let pattern = parse("#Life 1.05\n#P 0 0\n**\n#P 4 2\n*\n").unwrap();
+        assert_eq!((pattern.width(), pattern.height()), (5, 3));
+        assert_eq!(
+            pattern.live_cells().collect::<Vec<_>>(),
+            [(0, 0), (1, 0), (4, 2)]
+        );
+    }
+
+    #[test]
+    fn parses_106() {
+        let pattern = parse("#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n").unwrap();
+        assert_eq!(pattern, glider());
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut pattern = glider();
+        pattern.rule = Some("B3/S23".to_string());
+        pattern.comments = vec!["Glider".to_string()];
+        let read = parse(&write_105(&pattern)).unwrap();
+        assert_eq!(read, pattern);
+
+        // 1.06 keeps only the cells.
+        assert_eq!(parse(&write_106(&pattern)).unwrap(), glider());
+
+        // Wider than a line, so split into several blocks.
+        let mut wide = Pattern::new(200, 2);
+        for x in (0..200).step_by(7) {
+            wide.set(x, x % 2, true);
+        }
+        wide.set(199, 1, true);
+        let written = write_105(&wide);
+        assert!(written
+            .lines()
+            .all(|line| line.len() <= LINE_LENGTH as usize));
+        assert_eq!(parse(&written).unwrap(), wide);
+    }
+
+    #[test]
+    fn writes_rules_survival_first() {
+        let mut pattern = glider();
+        pattern.rule = Some("B36/S23".to_string());
+        assert!(write_105(&pattern).contains("\n#R 23/36\n"));
+        assert_eq!(
+            parse(&write_105(&pattern)).unwrap().rule.as_deref(),
+            Some("23/36")
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let error = |contents: &str| parse(contents).unwrap_err().to_string();
+        assert_eq!(
+            error("#Life 1.07\n"),
+            "line 1: unsupported version '#Life 1.07', expected 1.05 or 1.06"
+        );
+        assert_eq!(
+            error("0 0\n"),
+            "line 1: missing '#Life 1.05' or '#Life 1.06' header"
+        );
+        assert_eq!(
+            error("#Life 1.05\n#P 0\n*\n"),
+            "line 2: expected 'x y' coordinates, found '0'"
+        );
+        assert_eq!(
+            error("#Life 1.05\n.*o\n"),
+            "line 2: unexpected 'o', expected '.' or '*'"
+        );
+        assert_eq!(
+            error("#Life 1.06\n0 zero\n"),
+            "line 2: 'zero' is not a whole number"
+        );
+        assert_eq!(
+            error("#Life 1.06\n0 0 0\n"),
+            "line 2: expected 'x y' coordinates, found '0 0 0'"
+        );
+    }
+
+    #[test]
+    fn rejects_sparse_patterns() {
+        assert!(matches!(
+            parse("#Life 1.06\n0 0\n100000 100000\n"),
+            Err(PatternError::TooLarge {
+                width: 100001,
+                height: 100001
+            })
+        ));
+        // Wider than a u32, which used to wrap around to a tiny pattern.
+        assert!(matches!(
+            parse("#Life 1.06\n0 0\n4294967296 0\n"),
+            Err(PatternError::TooLarge {
+                width: 4294967297,
+                height: 1
+            })
+        ));
+        // Wider than even an i64.
+        assert!(matches!(
+            parse("#Life 1.06\n-9223372036854775808 0\n9223372036854775807 0\n"),
+            Err(PatternError::TooLarge {
+                width: u64::MAX,
+                height: 1
+            })
+        ));
+        assert_eq!(
+            parse("#Life 1.05\n#P 9223372036854775807 0\n.*\n")
+                .unwrap_err()
+                .to_string(),
+            "line 3: cell coordinates are out of range"
+        );
+    }
+}
@@ -0,0 +1,389 @@
+//! Run Length Encoded patterns, the format used by Golly and LifeWiki.
+//!
+//! ```text
+//! #N Glider
+//! x = 3, y = 3, rule = B3/S23
+//! bob$2bo$3o!
+//! ```
+//!
+//! Multi-state patterns, such as those for Generations rules, use `.` for
+//! dead cells and `A`..`X` for states 1 to 24, with a prefix of `p`..`y` for
+//! each further 24 states.
+
+use super::{Pattern, PatternError, MAX_CELLS};
+
+/// Longest line `write` produces, as recommended by the format.
+const LINE_LENGTH: usize = 70;
+
+pub fn parse(contents: &str) -> Result<Pattern, PatternError> {
+    let mut comments = Vec::new();
+    let mut header = None;
+    let mut cells = Vec::new();
+    let (mut x, mut y) = (0i64, 0i64);
+    let mut run: Option<i64> = None;
+    let mut prefix: Option<char> = None;
+    let mut finished = false;
+
+    for (i, line) in contents.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim();
+        if finished {
+            break;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            // #N name, #C/#c comment, #O author; anything else is ignored.
+            let mut chars = comment.chars();
+            if let Some('N' | 'C' | 'c' | 'O') = chars.next() {
+                comments.push(chars.as_str().trim().to_string());
+            }
+            continue;
+        }
+        if header.is_none() && line.starts_with('x') {
+            header = Some(parse_header(line).map_err(|m| PatternError::parse(number, m))?);
+            continue;
+        }
+
+        for c in line.chars() {
+            if let Some(p) = prefix {
+                if !c.is_ascii_uppercase() || c > 'X' {
+                    return Err(PatternError::parse(
+                        number,
+                        format!("expected a state after '{p}', found '{c}'"),
+                    ));
+                }
+            }
+            match c {
+                '0'..='9' => {
+                    let digit = c.to_digit(10).unwrap() as i64;
+                    let count = run.unwrap_or(0) * 10 + digit;
+                    if count > u32::MAX as i64 {
+                        return Err(PatternError::parse(number, "run count is too large"));
+                    }
+                    run = Some(count);
+                }
+                'b' | '.' => {
+                    x += run.take().unwrap_or(1);
+                }
+                '$' => {
+                    y += run.take().unwrap_or(1);
+                    x = 0;
+                }
+                '!' => {
+                    finished = true;
+                    break;
+                }
+                'p'..='y' => prefix = Some(c),
+                // `o` in two state patterns, `A` for the live state of multi
+                // state ones and the later letters for the decaying states.
+                'o' | 'A'..='X' => {
+                    let state = match (c, prefix.take()) {
+                        ('o', _) => 1,
+                        (c, p) => {
+                            let block = p.map_or(0, |p| p as u32 - 'p' as u32 + 1);
+                            block * 24 + c as u32 - 'A' as u32 + 1
+                        }
+                    };
+                    for _ in 0..run.take().unwrap_or(1) {
+                        if let Some((width, height, _)) = &header {
+                            if x >= *width as i64 || y >= *height as i64 {
+                                return Err(PatternError::parse(
+                                    number,
+                                    format!("pattern is larger than the {width}x{height} header"),
+                                ));
+                            }
+                        }
+                        // A long enough run would otherwise fill memory before
+                        // the size is ever checked.
+                        if cells.len() >= MAX_CELLS {
+                            return Err(PatternError::parse(
+                                number,
+                                format!("pattern has more than {MAX_CELLS} live or decaying cells"),
+                            ));
+                        }
+                        cells.push((x, y, state));
+                        x += 1;
+                    }
+                }
+                c if c.is_whitespace() => {}
+                c => {
+                    return Err(PatternError::parse(
+                        number,
+                        format!("unexpected '{c}' in pattern data"),
+                    ))
+                }
+            }
+        }
+    }
+
+    let mut pattern = match header {
+        Some((width, height, rule)) => {
+            let mut pattern = Pattern::try_new(width, height)?;
+            for &(x, y, state) in &cells {
+                pattern.set_state(x as u32, y as u32, state);
+            }
+            pattern.rule = rule;
+            pattern
+        }
+        None => Pattern::from_states(&cells)?,
+    };
+    pattern.comments = comments;
+    Ok(pattern)
+}
+
+/// Parses `x = 3, y = 3, rule = B3/S23`, the rule being optional.
+fn parse_header(line: &str) -> Result<(u32, u32, Option<String>), String> {
+    let mut width = None;
+    let mut height = None;
+    let mut rule = None;
+    for part in line.split(',') {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("expected `key = value` in header, found '{}'", part.trim()))?;
+        let value = value.trim();
+        match key.trim() {
+            "x" => width = Some(parse_size(value)?),
+            "y" => height = Some(parse_size(value)?),
+            "rule" => rule = Some(value.to_string()),
+            key => return Err(format!("unknown header field '{key}'")),
+        }
+    }
+    match (width, height) {
+        (Some(width), Some(height)) => Ok((width, height, rule)),
+        _ => Err("header needs both x and y".to_string()),
+    }
+}
+
+fn parse_size(value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("'{value}' is not a valid size"))
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = String::new();
+    for comment in &pattern.comments {
+        out.push_str(&format!("#C {comment}\n"));
+    }
+    out.push_str(&format!(
+        "x = {}, y = {}",
+        pattern.width(),
+        pattern.height()
+    ));
+    if let Some(rule) = &pattern.rule {
+        out.push_str(&format!(", rule = {rule}"));
+    }
+    out.push('\n');
+
+    // Two state patterns keep to `b` and `o`, the letters are only used when
+    // some cells are decaying.
+    let multi_state =
+        (0..pattern.height()).any(|y| (0..pattern.width()).any(|x| pattern.state(x, y) > 1));
+    let tag = |state: u32| match (state, multi_state) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (state, true) => {
+            let (block, letter) = ((state - 1) / 24, (state - 1) % 24);
+            let letter = char::from(b'A' + letter as u8);
+            match block {
+                0 => letter.to_string(),
+                block => format!("{}{letter}", char::from(b'o' + block as u8)),
+            }
+        }
+    };
+
+    let mut line = String::new();
+    let mut push = |count: u32, tag: &str| {
+        let item = match count {
+            1 => tag.to_string(),
+            n => format!("{n}{tag}"),
+        };
+        if line.len() + item.len() > LINE_LENGTH {
+            out.push_str(&line);
+            out.push('\n');
+            line.clear();
+        }
+        line.push_str(&item);
+    };
+
+    // Blank rows are folded into the `$` count of the next non blank one, and
+    // dead cells at the end of a row are never written.
+    let mut last_row = None;
+    for y in 0..pattern.height() {
+        let mut runs = Vec::new();
+        for x in 0..pattern.width() {
+            let state = pattern.state(x, y);
+            match runs.last_mut() {
+                Some((last, count)) if *last == state => *count += 1,
+                _ => runs.push((state, 1)),
+            }
+        }
+        if let Some((0, _)) = runs.last() {
+            runs.pop();
+        }
+        if runs.is_empty() {
+            continue;
+        }
+        let skipped = match last_row {
+            Some(last) => y - last,
+            None => y,
+        };
+        if skipped > 0 {
+            push(skipped, "$");
+        }
+        last_row = Some(y);
+        for (state, count) in runs {
+            push(count, &tag(state));
+        }
+    }
+    push(1, "!");
+    out.push_str(&line);
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+
+    const GLIDER: &str = "#N Glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+
+    fn glider() -> Pattern {
+        let mut pattern = Pattern::new(3, 3);
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            pattern.set(x, y, true);
+        }
+        pattern
+    }
+
+    fn parse_error(contents: &str) -> String {
+        parse(contents).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_a_glider() {
+        let pattern = parse(GLIDER).unwrap();
+        assert_eq!(pattern.comments, ["Glider"]);
+        assert_eq!(pattern.rule.as_deref(), Some("B3/S23"));
+        assert_eq!(
+            Pattern {
+                rule: None,
+                comments: Vec::new(),
+                ..pattern
+            },
+            glider()
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut pattern = glider();
+        pattern.rule = Some("B3/S23".to_string());
+        pattern.comments = vec!["Glider".to_string()];
+        assert_eq!(parse(&write(&pattern)).unwrap(), pattern);
+
+        // Blank rows, trailing dead cells and lines longer than the limit.
+        let mut sparse = Pattern::new(200, 5);
+        for x in (0..200).step_by(3) {
+            sparse.set(x, 4, true);
+        }
+        sparse.set(199, 0, true);
+        let written = write(&sparse);
+        assert!(written.lines().all(|line| line.len() <= LINE_LENGTH));
+        assert_eq!(parse(&written).unwrap(), sparse);
+    }
+
+    #[test]
+    fn parses_multi_state_patterns() {
+        let pattern = parse("#N Brian's Brain\nx = 3, y = 2, rule = B2/S/C3\nAB$.BA!\n").unwrap();
+        assert_eq!(pattern.rule.as_deref(), Some("B2/S/C3"));
+        let states = |y| (0..3).map(|x| pattern.state(x, y)).collect::<Vec<_>>();
+        assert_eq!((states(0), states(1)), (vec![1, 2, 0], vec![0, 2, 1]));
+        // Only `A` is alive, `B` is a generation into decaying.
+        assert_eq!(pattern.live_cells().collect::<Vec<_>>(), [(0, 0), (2, 1)]);
+        let mut grid = Grid::new(3, 2);
+        pattern.place(&mut grid, 0, 0, 2);
+        assert_eq!(grid.cells(), [2, 1, 0, 0, 1, 2]);
+
+        // States past `X` take a prefix, and decay to nothing when there are
+        // more states than the lifetime.
+        let pattern = parse("X2pA.yO!").unwrap();
+        let states = (0..5).map(|x| pattern.state(x, 0)).collect::<Vec<_>>();
+        assert_eq!(states, [24, 25, 25, 0, 255]);
+        let values = (0..5).map(|x| pattern.value(x, 0, 30)).collect::<Vec<_>>();
+        assert_eq!(values, [7, 6, 6, 0, 0]);
+    }
+
+    #[test]
+    fn round_trips_multi_state_patterns() {
+        let pattern = parse("x = 6, y = 2\nAB2.pA$3.yOX!").unwrap();
+        let written = write(&pattern);
+        assert_eq!(written, "x = 6, y = 2\nAB2.pA$3.yOX!\n");
+        assert_eq!(parse(&written).unwrap(), pattern);
+        // Trimming keeps the decaying cells too.
+        let trimmed = parse("x = 5, y = 3\n$.B2A!").unwrap().trimmed();
+        assert_eq!(write(&trimmed), "x = 3, y = 1\nB2A!\n");
+    }
+
+    #[test]
+    fn header_is_optional() {
+        let pattern = parse("bo$2bo$3o!").unwrap();
+        assert_eq!(pattern, glider());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(
+            parse_error("x = 3, y = 3\nbo$2bz$3o!"),
+            "line 2: unexpected 'z' in pattern data"
+        );
+        assert_eq!(
+            parse_error("x = 3\nbo$2bo$3o!"),
+            "line 1: header needs both x and y"
+        );
+        assert_eq!(
+            parse_error("x = 3, y = three\n3o!"),
+            "line 1: 'three' is not a valid size"
+        );
+        assert_eq!(
+            parse_error("x = 3, y = 3, z = 1\n3o!"),
+            "line 1: unknown header field 'z'"
+        );
+        assert_eq!(
+            parse_error("x = 2, y = 3\nbo$2bo$3o!"),
+            "line 2: pattern is larger than the 2x3 header"
+        );
+        assert_eq!(
+            parse_error("A.p2B!"),
+            "line 1: expected a state after 'p', found '2'"
+        );
+        assert_eq!(
+            parse_error("A.qY!"),
+            "line 1: expected a state after 'q', found 'Y'"
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_patterns() {
+        assert_eq!(
+            parse_error("x = 4294967296, y = 1\no!"),
+            "line 1: '4294967296' is not a valid size"
+        );
+        assert!(matches!(
+            parse("x = 100000, y = 100000\no!"),
+            Err(PatternError::TooLarge {
+                width: 100000,
+                height: 100000
+            })
+        ));
+        assert!(matches!(
+            parse("x = 4294967295, y = 4294967295\no!"),
+            Err(PatternError::TooLarge { .. })
+        ));
+        assert_eq!(
+            parse_error("99999999999o!"),
+            "line 1: run count is too large"
+        );
+    }
+}
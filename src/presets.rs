@@ -0,0 +1,256 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::life::Params;
+use crate::rule::Rule;
+
+/// A named rule, optionally with its own lifetime for rules that look best
+/// with a particular amount of decay.
+#[derive(Clone, Debug)]
+pub struct Preset {
+    pub name: String,
+    pub rule: Rule,
+    pub lifetime: Option<u32>,
+}
+
+impl Preset {
+    fn new(name: &str, rule: &str, lifetime: Option<u32>) -> Self {
+        Self {
+            name: name.to_string(),
+            rule: rule.parse().unwrap(),
+            lifetime,
+        }
+    }
+
+    pub fn params(&self, width: u32, height: u32, default_lifetime: u32) -> Params {
+        self.rule
+            .params(width, height, self.lifetime.unwrap_or(default_lifetime))
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.rule)
+    }
+}
+
+#[derive(Debug)]
+pub enum PresetError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::Io(e) => write!(f, "{e}"),
+            PresetError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+impl From<io::Error> for PresetError {
+    fn from(e: io::Error) -> Self {
+        PresetError::Io(e)
+    }
+}
+
+/// Registry of named rules, the built in ones followed by any loaded from
+/// preset files.
+#[derive(Clone, Debug)]
+pub struct Presets {
+    entries: Vec<Preset>,
+}
+
+impl Presets {
+    pub fn builtin() -> Self {
+        let entries = vec![
+            Preset::new("Life", "B3/S23", None),
+            Preset::new("HighLife", "B36/S23", None),
+            Preset::new("Day & Night", "B3678/S34678", None),
+            Preset::new("Seeds", "B2/S", None),
+            Preset::new("Life without Death", "B3/S012345678", None),
+            Preset::new("Replicator", "B1357/S1357", Some(100)),
+            Preset::new("Morley", "B368/S245", None),
+            Preset::new("2x2", "B36/S125", None),
+            Preset::new("Diamoeba", "B35678/S5678", None),
+            Preset::new("Maze", "B3/S12345", None),
+            Preset::new("Coral", "B3/S45678", None),
+            Preset::new("Anneal", "B4678/S35678", None),
+            Preset::new("Gnarl", "B1/S1", None),
+            Preset::new("Brian's Brain", "B2/S/C3", None),
+            Preset::new("Star Wars", "B2/S345/C4", None),
+            Preset::new("Fireworks", "B13/S2/C21", None),
+        ];
+        Self { entries }
+    }
+
+    /// Adds the presets in a file to the registry, a preset with the same name
+    /// as an existing one replaces it.
+    ///
+    /// Each line has the form `name = rule [lifetime]`, blank lines and lines
+    /// starting with `#` are ignored:
+    ///
+    /// ```text
+    /// # slow fading HighLife
+    /// Ghostly = B36/S23 5000
+    /// ```
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<(), PresetError> {
+        self.load_str(&fs::read_to_string(path)?)
+    }
+
+    /// `load_file` for contents already read.
+    fn load_str(&mut self, contents: &str) -> Result<(), PresetError> {
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let preset = parse_line(line).map_err(|message| PresetError::Parse {
+                line: i + 1,
+                message,
+            })?;
+            self.insert(preset);
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, preset: Preset) {
+        match self.position(&preset.name) {
+            Some(i) => self.entries[i] = preset,
+            None => self.entries.push(preset),
+        }
+    }
+
+    /// Finds a preset by name, ignoring case, spaces and punctuation so that
+    /// `brians-brain` matches `Brian's Brain`.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        let name = normalise(name);
+        self.entries.iter().position(|p| normalise(&p.name) == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Preset> {
+        self.entries.iter()
+    }
+}
+
+impl std::ops::Index<usize> for Presets {
+    type Output = Preset;
+
+    fn index(&self, index: usize) -> &Preset {
+        &self.entries[index]
+    }
+}
+
+fn normalise(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+fn parse_line(line: &str) -> Result<Preset, String> {
+    let (name, value) = line
+        .split_once('=')
+        .ok_or_else(|| "expected `name = rule [lifetime]`".to_string())?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("preset has no name".to_string());
+    }
+    let mut parts = value.split_whitespace();
+    let rule = parts
+        .next()
+        .ok_or_else(|| format!("preset '{name}' has no rule"))?
+        .parse::<Rule>()
+        .map_err(|e| e.to_string())?;
+    let lifetime = match parts.next() {
+        Some(lifetime) => match lifetime.parse::<u32>() {
+            Ok(lifetime) if lifetime > 0 => Some(lifetime),
+            _ => return Err(format!("'{lifetime}' is not a valid lifetime")),
+        },
+        None => None,
+    };
+    if let Some(extra) = parts.next() {
+        return Err(format!("unexpected '{extra}' after the lifetime"));
+    }
+    Ok(Preset {
+        name: name.to_string(),
+        rule,
+        lifetime,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(contents: &str) -> Result<Presets, String> {
+        let mut presets = Presets::builtin();
+        presets.load_str(contents).map_err(|e| e.to_string())?;
+        Ok(presets)
+    }
+
+    #[test]
+    fn builtin_presets_parse() {
+        // `Preset::new` panics on a bad rulestring.
+        let presets = Presets::builtin();
+        assert!(!presets.is_empty());
+        let brain = presets.position("brians-brain").unwrap();
+        assert_eq!(presets[brain].rule.states, Some(3));
+    }
+
+    #[test]
+    fn loads_files() {
+        let presets = load(
+            "# slow fading HighLife\n\
+             Ghostly = B36/S23 5000\n\
+             \n\
+             \x20 life = B3/S23/C4  \n",
+        )
+        .unwrap();
+        let ghostly = &presets[presets.position("ghostly").unwrap()];
+        assert_eq!(ghostly.rule, "B36/S23".parse().unwrap());
+        assert_eq!(ghostly.lifetime, Some(5000));
+        // Replaces the builtin Life rather than adding another.
+        assert_eq!(presets.len(), Presets::builtin().len() + 1);
+        let life = &presets[presets.position("Life").unwrap()];
+        assert_eq!((life.name.as_str(), life.rule.states), ("life", Some(4)));
+        assert_eq!(life.lifetime, None);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let error = |contents| load(contents).unwrap_err();
+        assert_eq!(
+            error("\nGhostly B36/S23"),
+            "line 2: expected `name = rule [lifetime]`"
+        );
+        assert_eq!(error(" = B3/S23"), "line 1: preset has no name");
+        assert_eq!(error("Ghostly ="), "line 1: preset 'Ghostly' has no rule");
+        assert_eq!(
+            error("Ghostly = B36/S23 0"),
+            "line 1: '0' is not a valid lifetime"
+        );
+        assert_eq!(
+            error("Ghostly = B36/S23 soon"),
+            "line 1: 'soon' is not a valid lifetime"
+        );
+        assert_eq!(
+            error("Ghostly = B36/S23 5000 more"),
+            "line 1: unexpected 'more' after the lifetime"
+        );
+        assert!(error("Ghostly = B9/S23").starts_with("line 1: "));
+    }
+}
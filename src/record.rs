@@ -0,0 +1,205 @@
+//! Offscreen recording of what the window shows, for making animations
+//! without a display.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use wgpu::{Buffer, Device, Queue, Texture};
+
+use crate::camera::Camera;
+use crate::image::{Image, ImageError};
+use crate::render::Renderer;
+
+/// Offscreen target format. Being sRGB the frames come out looking as they
+/// do in the window, which uses an sRGB surface format where it can.
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+enum Output {
+    /// Numbered PNG files in a directory.
+    Frames(PathBuf),
+    /// A single YUV4MPEG2 stream.
+    Y4m(BufWriter<File>),
+}
+
+/// Draws a simulation's output texture through the same `Renderer` pipeline
+/// as the window, into an offscreen texture that's read back for every
+/// captured frame.
+pub struct Recorder {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    renderer: Renderer,
+    target: Texture,
+    staging_buffer: Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    output: Output,
+    frames: u64,
+}
+
+impl Recorder {
+    /// Records `output_texture` at `width` by `height`, fitting the whole
+    /// grid into each frame. A `path` ending in
+    /// `.y4m` is written as one Y4M stream at `fps`, anything else is taken as
+    /// a directory for numbered PNG frames.
+    pub fn new(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        output_texture: &Texture,
+        width: u32,
+        height: u32,
+        path: &Path,
+        fps: u32,
+    ) -> Result<Self, ImageError> {
+        let output = if path.extension().is_some_and(|ext| ext == "y4m") {
+            let mut file = BufWriter::new(File::create(path)?);
+            // 4:4:4 keeps single cells sharp, subsampled chroma would smear
+            // them into their neighbours.
+            writeln!(file, "YUV4MPEG2 W{width} H{height} F{fps}:1 Ip A1:1 C444")?;
+            Output::Y4m(file)
+        } else {
+            fs::create_dir_all(path)?;
+            Output::Frames(path.to_path_buf())
+        };
+
+        let view = output_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let grid = output_texture.size();
+        let camera = Camera::fit((grid.width, grid.height), (width, height));
+        let renderer = Renderer::new(&device, view, FORMAT, &camera);
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Recording Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: FORMAT,
+            view_formats: &[],
+        });
+        // Texture to buffer copies need rows padded to a multiple of 256 bytes.
+        let padded_bytes_per_row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Recording Staging Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Ok(Self {
+            device,
+            queue,
+            renderer,
+            target,
+            staging_buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            output,
+            frames: 0,
+        })
+    }
+
+    /// Number of frames captured so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Renders the output texture as it is now and writes it out as the
+    /// next frame.
+    pub fn capture(&mut self) -> Result<(), ImageError> {
+        let image = pollster::block_on(self.read_frame());
+        match &mut self.output {
+            Output::Frames(dir) => {
+                image.save_png(dir.join(format!("frame-{:06}.png", self.frames)))?
+            }
+            Output::Y4m(file) => write_y4m_frame(file, &image)?,
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Flushes the Y4M stream, if that's what's being written.
+    pub fn finish(self) -> Result<(), ImageError> {
+        if let Output::Y4m(mut file) = self.output {
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    async fn read_frame(&self) -> Image {
+        let view = self
+            .target
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.renderer.draw(&view, &self.device, &self.queue);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            self.target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.staging_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(self.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = self.staging_buffer.slice(..);
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+
+        // Blocks until the draw and copy have finished and the map callback ran.
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .receive()
+            .await
+            .expect("staging buffer map callback dropped")
+            .expect("failed to map staging buffer");
+
+        let data = buffer_slice.get_mapped_range();
+        let pixels = data
+            .chunks_exact(self.padded_bytes_per_row as usize)
+            .flat_map(|row| row[..(self.width * 4) as usize].chunks_exact(4))
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect();
+        drop(data);
+        self.staging_buffer.unmap();
+        Image::from_pixels(self.width, self.height, pixels)
+    }
+}
+
+/// Writes one 4:4:4 frame, converting with the BT.601 studio range matrix
+/// players assume for Y4M.
+fn write_y4m_frame(file: &mut impl Write, image: &Image) -> std::io::Result<()> {
+    let mut planes = [Vec::new(), Vec::new(), Vec::new()];
+    for pixel in image.pixels().chunks_exact(3) {
+        let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.0);
+        let y = 16.0 + 65.481 * r + 128.553 * g + 24.966 * b;
+        let u = 128.0 - 37.797 * r - 74.203 * g + 112.0 * b;
+        let v = 128.0 + 112.0 * r - 93.786 * g - 18.214 * b;
+        for (plane, value) in planes.iter_mut().zip([y, u, v]) {
+            plane.push(value.round() as u8);
+        }
+    }
+    file.write_all(b"FRAME\n")?;
+    for plane in &planes {
+        file.write_all(plane)?;
+    }
+    Ok(())
+}
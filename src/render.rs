@@ -1,8 +1,13 @@
 use wgpu::{
-    util::DeviceExt, BindGroup, Buffer, Device, Queue, RenderPipeline, Surface,
-    SurfaceConfiguration, TextureView,
+    util::DeviceExt, BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPipeline, Sampler,
+    Surface, Texture, TextureFormat, TextureView,
 };
 
+use bytemuck::Zeroable;
+
+use crate::camera::Camera;
+use crate::pattern::Pattern;
+
 pub const VERTICES: &[Vertex] = &[
     Vertex {
         position: [1.0, 1.0, 0.0],
@@ -53,24 +58,50 @@ impl Vertex {
     }
 }
 
+/// Where the stamp preview and selection are drawn, as laid out for
+/// `shaders/shader.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OverlayUniform {
+    /// Cell under the top left corner of the stamp.
+    stamp_origin: [f32; 2],
+    /// Size of the stamp in cells, zero when there's no preview.
+    stamp_size: [f32; 2],
+    selection_origin: [f32; 2],
+    /// Size of the selection in cells, zero when nothing is selected.
+    selection_size: [f32; 2],
+}
+
 pub struct Renderer {
     render_pipeline: RenderPipeline,
     vertex_buffer: Buffer,
     index_buffer: Buffer,
     num_indices: u32,
+    output_texture_bind_group_layout: BindGroupLayout,
+    output_texture_sampler: Sampler,
     output_texture_bind_group: BindGroup,
+    camera_buffer: Buffer,
+    overlay_bind_group_layout: BindGroupLayout,
+    overlay_bind_group: BindGroup,
+    overlay_buffer: Buffer,
+    overlay: OverlayUniform,
 }
 
 impl Renderer {
+    /// Draws `output_texture_view` as seen by `camera` into render targets of
+    /// `format`, the surface format for a window.
     pub fn new(
         device: &Device,
         output_texture_view: TextureView,
-        window_config: &SurfaceConfiguration,
+        format: TextureFormat,
+        camera: &Camera,
     ) -> Self {
+        // Repeat makes the grid wrap around like the simulation when the
+        // camera looks past its edges.
         let output_texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
             mag_filter: wgpu::FilterMode::Nearest,
             min_filter: wgpu::FilterMode::Nearest,
             mipmap_filter: wgpu::FilterMode::Nearest,
@@ -98,23 +129,69 @@ impl Renderer {
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
                 label: Some("texture_bind_group_layout"),
             });
-        let output_texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &output_texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&output_texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&output_texture_sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::bytes_of(&camera.uniform()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        let output_texture_bind_group = create_output_texture_bind_group(
+            device,
+            &output_texture_bind_group_layout,
+            &output_texture_view,
+            &output_texture_sampler,
+            &camera_buffer,
+        );
+        let overlay_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("overlay_bind_group_layout"),
+            });
+        let overlay = OverlayUniform::zeroed();
+        let overlay_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overlay Buffer"),
+            contents: bytemuck::bytes_of(&overlay),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let overlay_bind_group = create_overlay_bind_group(
+            device,
+            &overlay_bind_group_layout,
+            &create_stamp_texture(device, 1, 1),
+            &overlay_buffer,
+        );
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/shader.wgsl").into()),
@@ -122,7 +199,10 @@ impl Renderer {
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&output_texture_bind_group_layout],
+                bind_group_layouts: &[
+                    &output_texture_bind_group_layout,
+                    &overlay_bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
         let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -137,7 +217,7 @@ impl Renderer {
                 module: &shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: window_config.format,
+                    format,
                     blend: Some(wgpu::BlendState {
                         color: wgpu::BlendComponent::REPLACE,
                         alpha: wgpu::BlendComponent::REPLACE,
@@ -184,9 +264,91 @@ impl Renderer {
             vertex_buffer,
             index_buffer,
             num_indices,
+            output_texture_bind_group_layout,
+            output_texture_sampler,
             output_texture_bind_group,
+            camera_buffer,
+            overlay_bind_group_layout,
+            overlay_bind_group,
+            overlay_buffer,
+            overlay,
         }
     }
+
+    /// Draws a different output texture from the next frame on, such as the
+    /// simulation's new one after it was resized.
+    pub fn set_output_texture(&mut self, device: &Device, output_texture_view: TextureView) {
+        self.output_texture_bind_group = create_output_texture_bind_group(
+            device,
+            &self.output_texture_bind_group_layout,
+            &output_texture_view,
+            &self.output_texture_sampler,
+            &self.camera_buffer,
+        );
+    }
+
+    /// Uses `camera` from the next frame on.
+    pub fn set_camera(&self, queue: &Queue, camera: &Camera) {
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::bytes_of(&camera.uniform()),
+        );
+    }
+
+    /// Previews `pattern` over the grid, or stops previewing with `None`.
+    /// Its live cells are highlighted and its dead ones dimmed.
+    pub fn set_stamp(&mut self, device: &Device, queue: &Queue, pattern: Option<&Pattern>) {
+        let empty = Pattern::new(0, 0);
+        let pattern = pattern.unwrap_or(&empty);
+        let (width, height) = (pattern.width(), pattern.height());
+        let texture = create_stamp_texture(device, width, height);
+        let mut texels = vec![0u8; (width * height) as usize];
+        for (x, y) in pattern.live_cells() {
+            texels[(y * width + x) as usize] = u8::MAX;
+        }
+        if !texels.is_empty() {
+            queue.write_texture(
+                texture.as_image_copy(),
+                &texels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(width),
+                    rows_per_image: None,
+                },
+                texture.size(),
+            );
+        }
+        self.overlay_bind_group = create_overlay_bind_group(
+            device,
+            &self.overlay_bind_group_layout,
+            &texture,
+            &self.overlay_buffer,
+        );
+        self.overlay.stamp_size = [width as f32, height as f32];
+        self.write_overlay(queue);
+    }
+
+    /// Moves the stamp preview's top left corner to the cell `origin`, which
+    /// may be off the grid and wraps around.
+    pub fn set_stamp_origin(&mut self, queue: &Queue, origin: (i64, i64)) {
+        self.overlay.stamp_origin = [origin.0 as f32, origin.1 as f32];
+        self.write_overlay(queue);
+    }
+
+    /// Highlights the `width` by `height` cells with their top left corner at
+    /// `x`, `y`, or nothing with `None`.
+    pub fn set_selection(&mut self, queue: &Queue, selection: Option<(i64, i64, u32, u32)>) {
+        let (x, y, width, height) = selection.unwrap_or_default();
+        self.overlay.selection_origin = [x as f32, y as f32];
+        self.overlay.selection_size = [width as f32, height as f32];
+        self.write_overlay(queue);
+    }
+
+    fn write_overlay(&self, queue: &Queue) {
+        queue.write_buffer(&self.overlay_buffer, 0, bytemuck::bytes_of(&self.overlay));
+    }
+
     pub fn render(
         &mut self,
         surface: &Surface,
@@ -197,7 +359,15 @@ impl Renderer {
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+        self.draw(&view, device, queue);
+        output.present();
 
+        Ok(())
+    }
+
+    /// Draws into any texture of the format given to `new`, such as an
+    /// offscreen one.
+    pub fn draw(&self, view: &TextureView, device: &Device, queue: &Queue) {
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
@@ -206,7 +376,7 @@ impl Renderer {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -223,14 +393,81 @@ impl Renderer {
 
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.output_texture_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.overlay_bind_group, &[]);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
             render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
         }
 
         queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-
-        Ok(())
     }
 }
+
+fn create_output_texture_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    view: &TextureView,
+    sampler: &Sampler,
+    camera_buffer: &Buffer,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: camera_buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("diffuse_bind_group"),
+    })
+}
+
+/// A texture for the stamp preview with one texel per cell. Textures can't
+/// be empty, so it's at least one by one.
+fn create_stamp_texture(device: &Device, width: u32, height: u32) -> Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Stamp Texture"),
+        size: wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+fn create_overlay_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    texture: &Texture,
+    buffer: &Buffer,
+) -> BindGroup {
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("overlay_bind_group"),
+    })
+}
@@ -0,0 +1,325 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::life::Params;
+
+/// Birth/survival rule for an outer totalistic automaton.
+///
+/// Parsed from the usual rulestring notations:
+/// - `B3/S23`, with the sections in either order
+/// - `23/3`, survival first as in the original Life notation
+/// - `B2/S/C3` or `/2/3`, Generations rules with a number of cell states
+///
+/// The number of states of a Generations rule maps onto the lifetime decay of
+/// [`Params`]: a born cell starts at `states - 1` and counts down to zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub birth: [u32; 9],
+    pub survival: [u32; 9],
+    pub states: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleError {
+    Empty,
+    InvalidNeighbourCount(char),
+    DuplicateNeighbourCount(char),
+    UnknownSection(char),
+    DuplicateSection(char),
+    MissingSection(char),
+    InvalidStates(String),
+    TooManySections,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::Empty => write!(f, "rule is empty"),
+            RuleError::InvalidNeighbourCount(c) => {
+                write!(f, "'{c}' is not a neighbour count between 0 and 8")
+            }
+            RuleError::DuplicateNeighbourCount(c) => {
+                write!(f, "neighbour count '{c}' appears twice in the same section")
+            }
+            RuleError::UnknownSection(c) => {
+                write!(f, "expected a section starting with B, S or C, found '{c}'")
+            }
+            RuleError::DuplicateSection(c) => write!(f, "section '{c}' appears more than once"),
+            RuleError::MissingSection(c) => write!(f, "rule is missing the '{c}' section"),
+            RuleError::InvalidStates(s) => {
+                write!(
+                    f,
+                    "'{s}' is not a valid number of states, expected at least 2"
+                )
+            }
+            RuleError::TooManySections => write!(f, "rule has too many '/' separated sections"),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl Rule {
+    /// Conway's Game of Life, B3/S23.
+    pub const LIFE: Rule = Rule::new([0, 0, 0, 1, 0, 0, 0, 0, 0], [0, 0, 1, 1, 0, 0, 0, 0, 0]);
+
+    pub const fn new(birth: [u32; 9], survival: [u32; 9]) -> Self {
+        Self {
+            birth,
+            survival,
+            states: None,
+        }
+    }
+
+    pub const fn generations(birth: [u32; 9], survival: [u32; 9], states: u32) -> Self {
+        Self {
+            birth,
+            survival,
+            states: Some(states),
+        }
+    }
+
+    /// Lifetime a born cell starts with, `default` unless the rule gives a
+    /// number of states.
+    pub fn lifetime(&self, default: u32) -> u32 {
+        match self.states {
+            Some(states) => states - 1,
+            None => default,
+        }
+    }
+
+    pub fn params(&self, width: u32, height: u32, default_lifetime: u32) -> Params {
+        Params::new(
+            width,
+            height,
+            self.lifetime(default_lifetime),
+            self.survival,
+            self.birth,
+        )
+    }
+}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(RuleError::Empty);
+        }
+        let sections = s.split('/').collect::<Vec<_>>();
+        if sections.len() > 3 {
+            return Err(RuleError::TooManySections);
+        }
+
+        let labelled = sections
+            .iter()
+            .any(|section| section.starts_with(|c: char| c.is_ascii_alphabetic()));
+        if labelled {
+            parse_labelled(&sections)
+        } else {
+            parse_numeric(&sections)
+        }
+    }
+}
+
+/// `B3/S23` and `B2/S/C3` style, every section starts with its letter.
+fn parse_labelled(sections: &[&str]) -> Result<Rule, RuleError> {
+    let mut birth = None;
+    let mut survival = None;
+    let mut states = None;
+    for section in sections {
+        let mut chars = section.chars();
+        let label = chars.next().map(|c| c.to_ascii_uppercase());
+        let rest = chars.as_str();
+        let slot = match label {
+            Some('B') => &mut birth,
+            Some('S') => &mut survival,
+            Some('C') | Some('G') => {
+                if states.is_some() {
+                    return Err(RuleError::DuplicateSection('C'));
+                }
+                states = Some(parse_states(rest)?);
+                continue;
+            }
+            Some(c) => return Err(RuleError::UnknownSection(c)),
+            None => return Err(RuleError::UnknownSection('/')),
+        };
+        if slot.is_some() {
+            return Err(RuleError::DuplicateSection(label.unwrap()));
+        }
+        *slot = Some(parse_neighbours(rest)?);
+    }
+    Ok(Rule {
+        birth: birth.ok_or(RuleError::MissingSection('B'))?,
+        survival: survival.ok_or(RuleError::MissingSection('S'))?,
+        states,
+    })
+}
+
+/// `23/3` and `345/2/4` style, survival then birth then optionally states.
+fn parse_numeric(sections: &[&str]) -> Result<Rule, RuleError> {
+    match sections {
+        [survival, birth] => Ok(Rule::new(
+            parse_neighbours(birth)?,
+            parse_neighbours(survival)?,
+        )),
+        [survival, birth, states] => Ok(Rule::generations(
+            parse_neighbours(birth)?,
+            parse_neighbours(survival)?,
+            parse_states(states)?,
+        )),
+        _ => Err(RuleError::MissingSection('B')),
+    }
+}
+
+fn parse_neighbours(s: &str) -> Result<[u32; 9], RuleError> {
+    let mut rules = [0; 9];
+    for c in s.chars() {
+        match c.to_digit(10) {
+            Some(n) if n <= 8 => {
+                if rules[n as usize] == 1 {
+                    return Err(RuleError::DuplicateNeighbourCount(c));
+                }
+                rules[n as usize] = 1;
+            }
+            _ => return Err(RuleError::InvalidNeighbourCount(c)),
+        }
+    }
+    Ok(rules)
+}
+
+fn parse_states(s: &str) -> Result<u32, RuleError> {
+    match s.parse::<u32>() {
+        Ok(states) if states >= 2 => Ok(states),
+        _ => Err(RuleError::InvalidStates(s.to_string())),
+    }
+}
+
+fn write_neighbours(f: &mut fmt::Formatter<'_>, rules: &[u32; 9]) -> fmt::Result {
+    for (n, rule) in rules.iter().enumerate() {
+        if *rule == 1 {
+            write!(f, "{n}")?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "B")?;
+        write_neighbours(f, &self.birth)?;
+        write!(f, "/S")?;
+        write_neighbours(f, &self.survival)?;
+        if let Some(states) = self.states {
+            write!(f, "/C{states}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neighbours(counts: &[usize]) -> [u32; 9] {
+        let mut rules = [0; 9];
+        for &n in counts {
+            rules[n] = 1;
+        }
+        rules
+    }
+
+    #[test]
+    fn parses_birth_survival() {
+        assert_eq!("B3/S23".parse(), Ok(Rule::LIFE));
+        assert_eq!("S23/B3".parse(), Ok(Rule::LIFE));
+        assert_eq!("b3/s23".parse(), Ok(Rule::LIFE));
+        assert_eq!(" B3/S23 ".parse(), Ok(Rule::LIFE));
+        assert_eq!(
+            "B36/S23".parse(),
+            Ok(Rule::new(neighbours(&[3, 6]), neighbours(&[2, 3])))
+        );
+        assert_eq!(
+            "B2/S".parse(),
+            Ok(Rule::new(neighbours(&[2]), neighbours(&[])))
+        );
+    }
+
+    #[test]
+    fn parses_survival_first_notation() {
+        assert_eq!("23/3".parse(), Ok(Rule::LIFE));
+        assert_eq!(
+            "/2".parse(),
+            Ok(Rule::new(neighbours(&[2]), neighbours(&[])))
+        );
+    }
+
+    #[test]
+    fn parses_generations() {
+        let brians_brain = Rule::generations(neighbours(&[2]), neighbours(&[]), 3);
+        assert_eq!("B2/S/C3".parse(), Ok(brians_brain));
+        assert_eq!("/2/3".parse(), Ok(brians_brain));
+        assert_eq!("B2/S/G3".parse(), Ok(brians_brain));
+        assert_eq!(brians_brain.lifetime(10), 2);
+        assert_eq!(Rule::LIFE.lifetime(10), 10);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!("".parse::<Rule>(), Err(RuleError::Empty));
+        assert_eq!("  ".parse::<Rule>(), Err(RuleError::Empty));
+        assert_eq!(
+            "B9/S23".parse::<Rule>(),
+            Err(RuleError::InvalidNeighbourCount('9'))
+        );
+        assert_eq!(
+            "B3x/S23".parse::<Rule>(),
+            Err(RuleError::InvalidNeighbourCount('x'))
+        );
+        assert_eq!(
+            "B33/S23".parse::<Rule>(),
+            Err(RuleError::DuplicateNeighbourCount('3'))
+        );
+        assert_eq!(
+            "B3/X23".parse::<Rule>(),
+            Err(RuleError::UnknownSection('X'))
+        );
+        assert_eq!(
+            "B3/S23/B2".parse::<Rule>(),
+            Err(RuleError::DuplicateSection('B'))
+        );
+        assert_eq!(
+            "B3/S2/C3/C4".parse::<Rule>(),
+            Err(RuleError::TooManySections)
+        );
+        assert_eq!("B3".parse::<Rule>(), Err(RuleError::MissingSection('S')));
+        assert_eq!("S23".parse::<Rule>(), Err(RuleError::MissingSection('B')));
+        assert_eq!("23".parse::<Rule>(), Err(RuleError::MissingSection('B')));
+        assert_eq!(
+            "B2/S/C1".parse::<Rule>(),
+            Err(RuleError::InvalidStates("1".to_string()))
+        );
+        assert_eq!(
+            "/2/1".parse::<Rule>(),
+            Err(RuleError::InvalidStates("1".to_string()))
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for s in [
+            "B3/S23",
+            "B36/S23",
+            "B2/S",
+            "B/S012345678",
+            "B2/S/C3",
+            "B3/S23/C8",
+        ] {
+            let rule = s.parse::<Rule>().unwrap();
+            assert_eq!(rule.to_string(), s);
+            assert_eq!(rule.to_string().parse(), Ok(rule));
+        }
+        assert_eq!("23/3".parse::<Rule>().unwrap().to_string(), "B3/S23");
+    }
+}
@@ -0,0 +1,180 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::grid::Grid;
+use crate::image::Image;
+
+/// Symmetry of a random soup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    /// Unchanged by a half turn.
+    C2,
+    /// Unchanged by a quarter turn, needs a square soup.
+    C4,
+    /// Unchanged by quarter turns and reflections, needs a square soup.
+    D8,
+}
+
+impl Symmetry {
+    /// Whether the soup has to be square for this symmetry.
+    pub fn needs_square(self) -> bool {
+        matches!(self, Symmetry::C4 | Symmetry::D8)
+    }
+
+    /// The cells `x`, `y` is mapped to by the symmetry in a `size` soup,
+    /// including itself.
+    fn orbit(self, x: u32, y: u32, (width, height): (u32, u32)) -> Vec<(u32, u32)> {
+        let (w, h) = (width - 1, height - 1);
+        match self {
+            Symmetry::None => vec![(x, y)],
+            Symmetry::C2 => vec![(x, y), (w - x, h - y)],
+            Symmetry::C4 => vec![(x, y), (w - y, x), (w - x, h - y), (y, h - x)],
+            Symmetry::D8 => vec![
+                (x, y),
+                (w - y, x),
+                (w - x, h - y),
+                (y, h - x),
+                (w - x, y),
+                (x, h - y),
+                (y, x),
+                (w - y, h - x),
+            ],
+        }
+    }
+}
+
+/// Settings for a reproducible random soup.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Soup {
+    /// The same seed and settings always give the same soup.
+    pub seed: u64,
+    /// Chance of each cell being alive, between 0 and 1.
+    pub density: f64,
+    /// Side of a square of soup in the middle of an otherwise empty grid,
+    /// instead of filling the whole grid.
+    pub size: Option<u32>,
+    pub symmetry: Symmetry,
+    /// Gives live cells a random value between 1 and the lifetime, rather
+    /// than all starting fully alive.
+    pub random_lifetimes: bool,
+}
+
+impl Soup {
+    /// A soup filling the whole grid at 50% density.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            density: 0.5,
+            size: None,
+            symmetry: Symmetry::None,
+            random_lifetimes: false,
+        }
+    }
+}
+
+/// Random soup as described by `soup`.
+///
+/// Cells are drawn from the seeded generator row by row across the soup
+/// region, so a given seed only reproduces a soup with the same settings and
+/// region size. A symmetric soup draws every cell too, then copies the first
+/// cell of each orbit over the rest.
+pub fn soup(width: u32, height: u32, lifetime: u32, soup: &Soup) -> Vec<u32> {
+    let size = match soup.size {
+        Some(side) => (side.min(width), side.min(height)),
+        None => (width, height),
+    };
+    assert!(
+        !soup.symmetry.needs_square() || size.0 == size.1,
+        "{:?} symmetry needs a square soup",
+        soup.symmetry
+    );
+
+    let mut rng = StdRng::seed_from_u64(soup.seed);
+    let drawn = (0..size.0 * size.1)
+        .map(|_| match rng.gen_bool(soup.density) {
+            true if soup.random_lifetimes => rng.gen_range(1..=lifetime),
+            true => lifetime,
+            false => 0,
+        })
+        .collect::<Vec<_>>();
+
+    let mut grid = Grid::new(width, height);
+    let (left, top) = ((width - size.0) / 2, (height - size.1) / 2);
+    for y in 0..size.1 {
+        for x in 0..size.0 {
+            let (ox, oy) = soup
+                .symmetry
+                .orbit(x, y, size)
+                .into_iter()
+                .min_by_key(|&(x, y)| (y, x))
+                .unwrap();
+            let value = drawn[(oy * size.0 + ox) as usize];
+            grid.set((left + x) as i64, (top + y) as i64, value);
+        }
+    }
+    grid.into_cells()
+}
+
+/// Empty grid with a single live cell in the middle.
+pub fn centre(width: u32, height: u32, lifetime: u32) -> Vec<u32> {
+    let mut data = vec![0; (width * height) as usize];
+    data[((height / 2) * width + width / 2) as usize] = lifetime;
+    data
+}
+
+/// Completely dead grid.
+pub fn empty(width: u32, height: u32) -> Vec<u32> {
+    vec![0; (width * height) as usize]
+}
+
+/// How pixel brightness becomes a cell value in `from_image`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Brightness {
+    /// Cells at least this bright (between 0 and 1) are alive, others empty.
+    Threshold(f32),
+    /// Brightness picks a value between empty and alive, so darker pixels
+    /// start part way through decaying.
+    Lifetime,
+}
+
+/// How an image is fitted to a grid of a different size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fit {
+    /// Stretched to cover the grid.
+    Scale,
+    /// Repeated from the top left corner at its own size.
+    Tile,
+}
+
+/// Grid drawn from the luminance of an image.
+pub fn from_image(
+    image: &Image,
+    width: u32,
+    height: u32,
+    lifetime: u32,
+    brightness: Brightness,
+    fit: Fit,
+) -> Vec<u32> {
+    if image.width() == 0 || image.height() == 0 {
+        return empty(width, height);
+    }
+    let pixel = |x: u32, y: u32| match fit {
+        Fit::Scale => (
+            (x as u64 * image.width() as u64 / width as u64) as u32,
+            (y as u64 * image.height() as u64 / height as u64) as u32,
+        ),
+        Fit::Tile => (x % image.width(), y % image.height()),
+    };
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (px, py) = pixel(x, y);
+            let luminance = image.luminance(px, py);
+            match brightness {
+                Brightness::Threshold(threshold) => (luminance >= threshold) as u32 * lifetime,
+                Brightness::Lifetime => (luminance * lifetime as f32).round() as u32,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,118 @@
+use wgpu::Texture;
+
+use crate::grid::{Grid, Resize};
+use crate::life::Params;
+use crate::rule::Rule;
+
+/// A cellular automaton engine. Implemented by the compute shader in
+/// `life::Life` and the reference `cpu::CpuLife`, so everything driving a
+/// simulation can be written once against this trait.
+pub trait Simulation {
+    fn params(&self) -> Params;
+
+    /// Swaps the rule (and lifetime) while keeping the current grid. The grid
+    /// size has to stay the same, see `resize`.
+    fn set_params(&mut self, params: Params);
+
+    /// Switches to `rule` with `lifetime` (unless the rule has its own number
+    /// of states), keeping the grid. When the lifetime changes every cell is
+    /// rescaled to it, so live cells stay alive and decaying ones stay about
+    /// as far through their decay.
+    fn set_rule(&mut self, rule: &Rule, lifetime: u32) {
+        let current = self.params();
+        let params = rule.params(current.width, current.height, lifetime);
+        let (from, to) = (current.lifetime(), params.lifetime());
+        if from == to {
+            self.set_params(params);
+            return;
+        }
+        let cells = self
+            .read_cells()
+            .into_iter()
+            .map(|cell| rescale(cell, from, to))
+            .collect::<Vec<_>>();
+        // Parameters first, so the redraw colours cells by the new lifetime.
+        self.set_params(params);
+        self.write_cells(&cells);
+    }
+
+    /// Number of generations stepped since the simulation was created, or
+    /// since the counter was last set.
+    fn generation(&self) -> u64;
+
+    /// Sets the generation counter, for carrying on from a snapshot.
+    fn set_generation(&mut self, generation: u64);
+
+    fn step(&mut self) {
+        self.step_n(1);
+    }
+
+    fn step_n(&mut self, n: u32);
+
+    /// The current generation, one value per cell in row major order.
+    fn read_cells(&mut self) -> Vec<u32>;
+
+    fn read_grid(&mut self) -> Grid {
+        let params = self.params();
+        Grid::from_cells(params.width, params.height, self.read_cells())
+    }
+
+    /// Copy of the `width` by `height` region with its top left corner at `x`,
+    /// `y`, wrapping around the edges.
+    fn read_region(&mut self, x: i64, y: i64, width: u32, height: u32) -> Grid {
+        self.read_grid().region(x, y, width, height)
+    }
+
+    /// Overwrites the region the size of `cells` with its top left corner at
+    /// `x`, `y`, wrapping around the edges. It can't be larger than the grid.
+    fn write_region(&mut self, x: i64, y: i64, cells: &Grid) {
+        let mut grid = self.read_grid();
+        assert!(cells.width() <= grid.width() && cells.height() <= grid.height());
+        for dy in 0..cells.height() as i64 {
+            for dx in 0..cells.width() as i64 {
+                grid.set(x + dx, y + dy, cells.get(dx, dy));
+            }
+        }
+        self.write_cells(grid.cells());
+    }
+
+    /// Replaces every cell, `cells` has to match the grid size.
+    fn write_cells(&mut self, cells: &[u32]);
+
+    /// Sets each of the cells at `positions` to `value`, redrawing once for
+    /// the lot. Positions have to be on the grid.
+    fn set_cells(&mut self, positions: &[(u32, u32)], value: u32);
+
+    /// Goes back to the cells the simulation was created with, resized along
+    /// with the grid, and a generation counter of zero. The rule stays as it
+    /// is.
+    fn reset(&mut self);
+
+    /// Changes the grid size, carrying on from `cells`. The cells `reset`
+    /// goes back to are resized as `resize` says.
+    fn resize(&mut self, width: u32, height: u32, cells: Vec<u32>, resize: Resize);
+
+    /// Changes the grid size, keeping the current cells as `resize` says.
+    fn resize_to(&mut self, width: u32, height: u32, resize: Resize) {
+        let cells = self.read_grid().resized(width, height, resize);
+        self.resize(width, height, cells.into_cells(), resize);
+    }
+
+    /// Texture coloured with every generation for the renderer to draw, if
+    /// this simulation has one.
+    fn output_texture(&self) -> Option<&Texture>;
+}
+
+/// Maps a cell of a simulation with lifetime `from` onto lifetime `to`, the
+/// way `Simulation::set_rule` does.
+pub fn rescale(cell: u32, from: u32, to: u32) -> u32 {
+    match cell {
+        0 => 0,
+        cell if cell == from => to,
+        // Rounding up keeps decaying cells from vanishing, as long as there's
+        // a value between dead and alive to give them.
+        cell => (cell as u64 * to as u64)
+            .div_ceil(from as u64)
+            .min(to as u64 - 1) as u32,
+    }
+}
@@ -0,0 +1,364 @@
+//! Binary snapshots of the complete simulation state.
+//!
+//! Unlike pattern files these keep every cell value, so decaying cells survive
+//! a save and load, along with the `Params` and the generation counter. All
+//! fields are little endian `u32`s apart from the generation:
+//!
+//! ```text
+//! magic       8 bytes  "CONWAYSS"
+//! version     u32      2
+//! width       u32
+//! height      u32
+//! lifetime    u32
+//! alive rules 9 x u32
+//! dead rules  9 x u32
+//! states      u32      lifetime + 1 for Generations rules, otherwise 0
+//! generation  u64
+//! cells       width x height x u32, row major
+//! ```
+//!
+//! Version 1 is the same without the number of states, and is still read.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::grid;
+use crate::life::Params;
+use crate::rule::Rule;
+use crate::simulation::Simulation;
+
+const MAGIC: &[u8; 8] = b"CONWAYSS";
+const VERSION: u32 = 2;
+
+/// Size of everything before the cells.
+const HEADER_SIZE: usize = MAGIC.len() + 4 * (1 + 3 + 9 + 9 + 1) + 8;
+
+/// `HEADER_SIZE` for version 1, which has no number of states.
+const HEADER_SIZE_V1: usize = HEADER_SIZE - 4;
+
+#[derive(Clone)]
+pub struct Snapshot {
+    pub params: Params,
+    /// Number of states of a Generations rule, which `params` can't tell
+    /// apart from a B/S rule with the same lifetime. `capture` leaves it
+    /// empty, set it from the rule before saving.
+    pub states: Option<u32>,
+    pub generation: u64,
+    pub cells: Vec<u32>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    NotASnapshot,
+    UnsupportedVersion(u32),
+    /// The header holds values no simulation could have been saved with.
+    InvalidHeader(String),
+    /// The file ended before all `expected` bytes, header and cells, were read.
+    Truncated {
+        expected: usize,
+        found: usize,
+    },
+    TrailingData,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{e}"),
+            SnapshotError::NotASnapshot => write!(f, "not a snapshot file"),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot version {version} is not supported, expected at most {VERSION}"
+            ),
+            SnapshotError::InvalidHeader(message) => {
+                write!(f, "invalid snapshot header, {message}")
+            }
+            SnapshotError::Truncated { expected, found } => write!(
+                f,
+                "snapshot is truncated, expected {expected} bytes but found {found}"
+            ),
+            SnapshotError::TrailingData => write!(f, "unexpected data after the cells"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl Snapshot {
+    /// Reads the current state back from a simulation.
+    pub fn capture(life: &mut dyn Simulation) -> Self {
+        Self {
+            params: life.params(),
+            states: None,
+            generation: life.generation(),
+            cells: life.read_cells(),
+        }
+    }
+
+    /// Puts a simulation back into this state. The grid has to be the same
+    /// size, otherwise use `Simulation::resize` first.
+    pub fn restore(&self, life: &mut dyn Simulation) {
+        life.set_params(self.params);
+        life.write_cells(&self.cells);
+        life.set_generation(self.generation);
+    }
+
+    /// The rule the snapshot was taken with.
+    pub fn rule(&self) -> Rule {
+        Rule {
+            birth: self.params.dead_rules(),
+            survival: self.params.alive_rules(),
+            states: self.states,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.cells.len() * 4);
+        bytes.extend_from_slice(MAGIC);
+        let params = &self.params;
+        let header = [VERSION, params.width, params.height, params.lifetime()]
+            .into_iter()
+            .chain(params.alive_rules())
+            .chain(params.dead_rules())
+            .chain([self.states.unwrap_or(0)]);
+        for value in header {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.generation.to_le_bytes());
+        for cell in &self.cells {
+            bytes.extend_from_slice(&cell.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        let truncated = |expected| SnapshotError::Truncated {
+            expected,
+            found: bytes.len(),
+        };
+        let mut words = bytes[MAGIC.len()..]
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()));
+        let version = words.next().ok_or_else(|| truncated(HEADER_SIZE))?;
+        let header_size = match version {
+            1 => HEADER_SIZE_V1,
+            VERSION => HEADER_SIZE,
+            _ => return Err(SnapshotError::UnsupportedVersion(version)),
+        };
+        let mut next = || words.next().ok_or_else(|| truncated(header_size));
+
+        let (width, height, lifetime) = (next()?, next()?, next()?);
+        let mut alive_rules = [0; 9];
+        for rule in &mut alive_rules {
+            *rule = next()?;
+        }
+        let mut dead_rules = [0; 9];
+        for rule in &mut dead_rules {
+            *rule = next()?;
+        }
+        let states = if version == 1 { 0 } else { next()? };
+        let generation = next()? as u64 | (next()? as u64) << 32;
+
+        let invalid = |message: String| Err(SnapshotError::InvalidHeader(message));
+        if width == 0 || height == 0 {
+            return invalid(format!("the grid can't be {width}x{height}"));
+        }
+        if width as u64 * height as u64 > grid::MAX_CELLS {
+            return invalid(format!("a {width}x{height} grid is too large"));
+        }
+        if lifetime == 0 {
+            return invalid("the lifetime can't be 0".to_string());
+        }
+        if alive_rules.iter().chain(&dead_rules).any(|&rule| rule > 1) {
+            return invalid("rule entries have to be 0 or 1".to_string());
+        }
+        let states = match states {
+            0 => None,
+            states if states >= 2 && states - 1 == lifetime => Some(states),
+            states => {
+                return invalid(format!(
+                    "{states} states don't match a lifetime of {lifetime}"
+                ))
+            }
+        };
+        let Some(expected) = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|cells| cells.checked_mul(4))
+            .and_then(|size| size.checked_add(header_size))
+        else {
+            return invalid(format!("a {width}x{height} grid is too large"));
+        };
+        match bytes.len() {
+            len if len < expected => return Err(truncated(expected)),
+            len if len > expected => return Err(SnapshotError::TrailingData),
+            _ => {}
+        }
+        let cells = bytes[header_size..]
+            .chunks_exact(4)
+            .map(|cell| u32::from_le_bytes(cell.try_into().unwrap()))
+            .collect();
+
+        Ok(Self {
+            params: Params::new(width, height, lifetime, alive_rules, dead_rules),
+            states,
+            generation,
+            cells,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            params: Rule::LIFE.params(3, 2, 5),
+            states: None,
+            generation: (7 << 32) | 42,
+            cells: vec![0, 5, 4, 3, 0, 1],
+        }
+    }
+
+    /// `snapshot` with the header word at `index`, counting the version as
+    /// 0, replaced by `value`.
+    fn with_header_word(index: usize, value: u32) -> Vec<u8> {
+        let mut bytes = snapshot().to_bytes();
+        let offset = MAGIC.len() + index * 4;
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        bytes
+    }
+
+    fn error(bytes: &[u8]) -> SnapshotError {
+        match Snapshot::from_bytes(bytes) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let bytes = snapshot().to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE + 6 * 4);
+        let read = Snapshot::from_bytes(&bytes).unwrap();
+        assert_eq!((read.params.width, read.params.height), (3, 2));
+        assert_eq!(read.params.lifetime(), 5);
+        assert_eq!(read.params.alive_rules(), Rule::LIFE.survival);
+        assert_eq!(read.params.dead_rules(), Rule::LIFE.birth);
+        assert_eq!(read.generation, snapshot().generation);
+        assert_eq!(read.cells, snapshot().cells);
+        assert_eq!(read.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn keeps_the_number_of_states() {
+        let rule = "B2/S/C6".parse::<Rule>().unwrap();
+        let snapshot = Snapshot {
+            params: rule.params(3, 2, 1000),
+            states: rule.states,
+            ..snapshot()
+        };
+        let read = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+        assert_eq!(read.rule(), rule);
+        assert_eq!(read.params.lifetime(), 5);
+    }
+
+    #[test]
+    fn reads_version_1() {
+        // Version 1 has no states between the dead rules and the generation.
+        let mut bytes = with_header_word(0, 1);
+        let states = MAGIC.len() + 22 * 4;
+        bytes.drain(states..states + 4);
+        assert_eq!(bytes.len(), HEADER_SIZE_V1 + 6 * 4);
+        let read = Snapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(read.rule(), Rule::LIFE);
+        assert_eq!(read.generation, snapshot().generation);
+        assert_eq!(read.cells, snapshot().cells);
+        assert!(matches!(
+            error(&bytes[..HEADER_SIZE_V1 - 1]),
+            SnapshotError::Truncated {
+                expected: HEADER_SIZE_V1,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(error(b""), SnapshotError::NotASnapshot));
+        assert!(matches!(error(b"CONWAY"), SnapshotError::NotASnapshot));
+        let mut bytes = snapshot().to_bytes();
+        bytes[0] = b'X';
+        assert!(matches!(error(&bytes), SnapshotError::NotASnapshot));
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        assert!(matches!(
+            error(&with_header_word(0, 3)),
+            SnapshotError::UnsupportedVersion(3)
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = snapshot().to_bytes();
+        assert!(matches!(
+            error(&bytes[..HEADER_SIZE - 1]),
+            SnapshotError::Truncated {
+                expected: HEADER_SIZE,
+                ..
+            }
+        ));
+        let expected = bytes.len();
+        assert!(matches!(
+            error(&bytes[..expected - 1]),
+            SnapshotError::Truncated { expected: e, found } if e == expected && found == expected - 1
+        ));
+
+        let mut bytes = bytes;
+        bytes.push(0);
+        assert!(matches!(error(&bytes), SnapshotError::TrailingData));
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let invalid = |bytes: Vec<u8>| matches!(error(&bytes), SnapshotError::InvalidHeader(_));
+        // Width, height and lifetime follow the version.
+        assert!(invalid(with_header_word(1, 0)));
+        assert!(invalid(with_header_word(2, 0)));
+        assert!(invalid(with_header_word(3, 0)));
+        // Then the alive and dead rules.
+        assert!(invalid(with_header_word(4, 2)));
+        assert!(invalid(with_header_word(21, u32::MAX)));
+        // Then the number of states, one more than the lifetime of 5 if any.
+        assert!(invalid(with_header_word(22, 5)));
+        assert!(invalid(with_header_word(22, 1)));
+        assert!(Snapshot::from_bytes(&with_header_word(22, 6)).is_ok());
+        // Grids with more cells than a u32 can index.
+        let mut bytes = with_header_word(1, u32::MAX);
+        bytes[MAGIC.len() + 8..][..4].copy_from_slice(&2u32.to_le_bytes());
+        assert!(invalid(bytes));
+    }
+}